@@ -1,3 +1,119 @@
-fn main() {
-    println!("Hello, world!");
+use std::{path::PathBuf, process::ExitCode};
+
+use zekuromu::{data::{RawData, RawOperatorData}, operators::{native::NativeOperator, registry::OperatorRegistry, wasm::WasmPlugin}, raw};
+
+const USAGE: &str = "Usage: reshiramu [--plugin <module.wasm>]... [--file-root <directory>] [--no-env] <file>...
+
+Merges the files in order, executes their operators, and prints the result as YAML.
+
+Options:
+    --plugin <module.wasm>     Loads the operators exported by a WebAssembly module
+    --file-root <directory>    Only lets operators read files under the directory
+    --no-env                   Makes any use of the 'env' operator an error
+    -h, --help                 Prints this message";
+
+struct Arguments {
+    plugins: Vec<PathBuf>,
+    file_root: Option<PathBuf>,
+    no_env: bool,
+    files: Vec<PathBuf>,
+}
+
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Option<Arguments>, String> {
+    let mut plugins = Vec::new();
+    let mut file_root = None;
+    let mut no_env = false;
+    let mut files = Vec::new();
+
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--plugin" => {
+                let plugin = arguments.next()
+                    .ok_or("'--plugin' expects a path to a module".to_owned())?;
+                plugins.push(plugin.into());
+            },
+            "--file-root" => {
+                let root = arguments.next()
+                    .ok_or("'--file-root' expects a path to a directory".to_owned())?;
+                file_root = Some(root.into());
+            },
+            "--no-env" => no_env = true,
+            _ if argument.starts_with('-') => return Err(format!("unknown option '{}'", argument)),
+            _ => files.push(argument.into()),
+        }
+    }
+
+    if files.is_empty() {
+        return Err("expected at least one file".to_owned())
+    }
+
+    Ok(Some(Arguments { plugins, file_root, no_env, files }))
+}
+
+fn run(arguments: Arguments) -> Result<String, Vec<String>> {
+    let mut registry = OperatorRegistry::new();
+    if let Some(root) = &arguments.file_root {
+        registry.set_file_root(root);
+    }
+    if arguments.no_env {
+        registry.disable(NativeOperator::Env);
+    }
+    for path in &arguments.plugins {
+        let plugin = WasmPlugin::load(path)
+            .map_err(|error| vec![format!("{}: {}", path.display(), error)])?;
+        plugin.register_into(&mut registry)
+            .map_err(|error| vec![format!("{}: {}", path.display(), error)])?;
+    }
+
+    // Files are merged once their operators know which file they come from.
+    let mut merged = RawOperatorData::Null;
+    for path in &arguments.files {
+        let data = raw::parse_file_as_raw_data(path)
+            .map_err(|error| vec![format!("{}: {}", path.display(), error)])?;
+        for lint in data.lint_operators(&registry) {
+            eprintln!("warning: {}: {}", path.display(), lint);
+        }
+        merged = merged.recursive_merge(data.into_raw_operator_data_from(path));
+    }
+
+    let mut operator_data = merged
+        .try_into_operator_data(&registry)
+        .map_err(|error| vec![error.to_string()])?;
+
+    operator_data.execute_operators()
+        .map_err(|errors| errors.iter().map(ToString::to_string).collect::<Vec<_>>())?;
+
+    let result = RawData::try_from(operator_data)
+        .map_err(|operator| vec![format!("operator left unexecuted: {:?}", operator.source())])?;
+
+    raw::serialize_raw_data_as_yaml(&result)
+        .map_err(|error| vec![error])
+}
+
+fn main() -> ExitCode {
+    let arguments = match parse_arguments(std::env::args().skip(1)) {
+        Ok(Some(arguments)) => arguments,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS
+        },
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::FAILURE
+        },
+    };
+
+    match run(arguments) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        },
+        Err(errors) => {
+            for error in errors {
+                eprintln!("error: {}", error);
+            }
+            ExitCode::FAILURE
+        },
+    }
 }
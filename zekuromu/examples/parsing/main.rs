@@ -1,14 +1,13 @@
 use std::{env, path::Path};
 
-use zekuromu;
 fn main() {
     let args: Vec<String> = env::args().collect();
     let first_arg_as_path = Path::new(&args[1]);
     let second_arg_as_path = Path::new(&args[2]);
 
-    let data = zekuromu::raw::parse_file_as_raw_data(&first_arg_as_path).unwrap();
-    let merge = zekuromu::raw::parse_file_as_raw_data(&second_arg_as_path).unwrap();
-    let mut merged = data.recursive_merge(merge);
+    let data = zekuromu::raw::parse_file_as_raw_data(first_arg_as_path).unwrap();
+    let merge = zekuromu::raw::parse_file_as_raw_data(second_arg_as_path).unwrap();
+    let merged = data.recursive_merge(merge);
 
     println!("{:?}", merged.into_raw_operator_data());
 }
@@ -1,11 +1,11 @@
 pub mod operators;
 
-use std::{collections::{HashMap, HashSet}, fmt::Display, hash::Hash, num::ParseIntError};
+use std::{collections::{HashMap, HashSet}, fmt::Display, hash::Hash, num::ParseIntError, path::Path, rc::Rc};
 
-use crate::{data::operators::Reference, operators::{Operator, OperatorExecutionErrorReason, OperatorParsingError}};
+use crate::{data::operators::{Reference, ReferenceAnchor, ReferenceSegment}, operators::{Bindings, EvaluationContext, Operator, OperatorEffect, OperatorExecutionErrorReason, OperatorParsingErrorReason, OperatorPriority, OperatorSource, registry::OperatorRegistry}};
 
 // Explicitely constrains `Mapping` to only use Strings as keys.
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct DataKey(String);
 
@@ -17,6 +17,12 @@ where T: Into<String>
     }
 }
 
+impl DataKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl TryInto<usize> for DataKey {
     type Error = ParseIntError;
     fn try_into(self) -> Result<usize, Self::Error> {
@@ -24,23 +30,66 @@ impl TryInto<usize> for DataKey {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DataKeyPath(Vec<DataKey>);
 
 impl DataKeyPath {
     pub fn empty() -> Self {
         DataKeyPath(vec![])
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn keys(&self) -> &[DataKey] {
+        &self.0
+    }
+
+    pub fn child(&self, key: impl Into<DataKey>) -> DataKeyPath {
+        let mut keys = self.0.clone();
+        keys.push(key.into());
+        DataKeyPath(keys)
+    }
+
+    pub fn starts_with(&self, other: &DataKeyPath) -> bool {
+        self.0.starts_with(&other.0)
+    }
+
+    /// This path followed by `other`, which is relative to it.
+    pub fn join(&self, other: &DataKeyPath) -> DataKeyPath {
+        DataKeyPath([self.0.as_slice(), other.0.as_slice()].concat())
+    }
+
+    /// `None` when going up more levels than the path is deep.
+    pub fn ancestor(&self, levels: usize) -> Option<DataKeyPath> {
+        let depth = self.0.len().checked_sub(levels)?;
+        Some(DataKeyPath(self.0[..depth].to_vec()))
+    }
+}
+
+// Bare when possible, quoted otherwise, so that it can be parsed back as a reference.
+impl Display for DataKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.0.is_empty() && self.0.chars().all(operators::is_bare_reference_char) {
+            return write!(f, "{}", self.0)
+        }
+
+        operators::write_quoted(f, &self.0)
+    }
 }
 
 impl Display for DataKeyPath {
-    // God this is terrible
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let joined = self.0.iter()
-            .map(|item| item.0.clone())
+            .map(|item| item.to_string())
             .collect::<Vec<String>>()
-            .join("\".\"");
-        write!(f, "\"{}\"", joined)
+            .join(".");
+        write!(f, "{}", joined)
     }
 }
 
@@ -50,12 +99,21 @@ impl From<DataKey> for DataKeyPath {
     }
 }
 
+// Relative references and negative indices can only be resolved against the data itself.
 impl TryFrom<Reference> for DataKeyPath {
     type Error = ();
     fn try_from(value: Reference) -> Result<Self, Self::Error> {
-        let mut builder = Vec::with_capacity(value.0.len());
-        for element in value.0 {
-            let data_key: DataKey = element.into();
+        if value.anchor != ReferenceAnchor::Root {
+            return Err(())
+        }
+
+        let mut builder = Vec::with_capacity(value.segments.len());
+        for element in value.segments {
+            let data_key: DataKey = match element {
+                ReferenceSegment::Key(inner) => inner.into(),
+                ReferenceSegment::Index(inner) if inner >= 0 => inner.to_string().into(),
+                ReferenceSegment::Index(_) | ReferenceSegment::Match { .. } => return Err(()),
+            };
             builder.push(data_key);
         }
         Ok(DataKeyPath(builder))
@@ -75,52 +133,66 @@ pub enum RawData {
     Mapping(HashMap<DataKey, RawData>)
 }
 
+// Keys of `other` override those of `mapping`, values found in both being merged with `merge`.
+// `other` replaces `mapping` altogether when it holds `(( replace-subtree ))` as a key.
+fn merge_mappings<T>(mut mapping: HashMap<DataKey, T>, mut other: HashMap<DataKey, T>, merge: fn(T, T) -> T) -> HashMap<DataKey, T> {
+    if other.keys().any(|key| operators::is_replace_marker(key.as_str())) {
+        return other
+    }
+
+    let keys: HashSet<_> = HashSet::from_iter(mapping
+        .keys()
+        .cloned()
+        .chain(
+            other
+            .keys()
+            .cloned()
+        ));
+
+    for key in keys {
+        if let Some(other_inner) = other.remove(&key) {
+            if let Some(self_inner) = mapping.remove(&key) {
+                mapping.insert(key, merge(self_inner, other_inner));
+            } else {
+                mapping.insert(key, other_inner);
+            }
+        }
+    }
+
+    mapping
+}
+
 impl RawData {
     pub fn recursive_merge(self, other: RawData) -> RawData {
-        match self {
-            RawData::Mapping(mut self_mapping) => {
-                if let RawData::Mapping(mut other_mapping) = other {
-                    let keys: HashSet<_> = HashSet::from_iter(self_mapping
-                        .keys()
-                        .cloned()
-                        .chain(
-                            other_mapping
-                            .keys()
-                            .cloned()
-                        ));
-
-                    for key in keys {
-                        if let Some(other_inner) = other_mapping.remove(&key) {
-                            if let Some(self_inner) = self_mapping.remove(&key) {
-                                self_mapping.insert(
-                                    key.clone(),
-                                    self_inner.recursive_merge(other_inner.clone())
-                                );
-                            } else {
-                                self_mapping.insert(
-                                    key.clone(),
-                                    other_inner
-                                );
-                            }
-                        }
-                    }
-
-                    RawData::Mapping(self_mapping)
-                } else {
-                    other
-                }
+        match (self, other) {
+            (RawData::Mapping(self_mapping), RawData::Mapping(other_mapping)) => {
+                RawData::Mapping(merge_mappings(self_mapping, other_mapping, RawData::recursive_merge))
             },
-            _ => other
+            (_, other) => other
         }
     }
 
     pub fn into_raw_operator_data(self) -> RawOperatorData {
+        self.into_raw_operator_data_with(None)
+    }
+
+    /// Operators remember they were read from `origin`, so that paths they hold are relative to it.
+    pub fn into_raw_operator_data_from(self, origin: &Path) -> RawOperatorData {
+        self.into_raw_operator_data_with(Some(&Rc::from(origin)))
+    }
+
+    fn into_raw_operator_data_with(self, origin: Option<&Rc<Path>>) -> RawOperatorData {
         match self {
             RawData::Null => RawOperatorData::Null,
             RawData::Boolean(inner) => RawOperatorData::Boolean(inner),
             RawData::Number(inner) => RawOperatorData::Number(inner),
             RawData::String(inner) => {
-                if let Some(expr) = operators::Expr::try_parse(&inner) {
+                if let Some(unescaped) = operators::unescape_operator_opening(&inner) {
+                    RawOperatorData::String(unescaped)
+                } else if let Some(mut expr) = operators::Expr::try_parse(&inner) {
+                    if let Some(origin) = origin {
+                        expr.set_origin(origin.clone());
+                    }
                     RawOperatorData::RawOperator(expr)
                 } else {
                     RawOperatorData::String(inner)
@@ -129,14 +201,14 @@ impl RawData {
             RawData::Sequence(inner) => {
                 let mut sequence = Vec::with_capacity(inner.len());
                 for item in inner {
-                    sequence.push(item.into_raw_operator_data());
+                    sequence.push(item.into_raw_operator_data_with(origin));
                 }
                 RawOperatorData::Sequence(sequence)
             },
             RawData::Mapping(inner) => {
                 let mut mapping = HashMap::with_capacity(inner.len());
                 for (inner_key, inner_value) in inner {
-                    mapping.insert(inner_key, inner_value.into_raw_operator_data());
+                    mapping.insert(inner_key, inner_value.into_raw_operator_data_with(origin));
                 }
                 RawOperatorData::Mapping(mapping)
             }
@@ -144,6 +216,99 @@ impl RawData {
     }
 }
 
+impl RawData {
+    /// Strings which look like operators but cannot be parsed, and would otherwise be silently kept as strings.
+    pub fn lint_operators(&self, registry: &OperatorRegistry) -> Vec<OperatorLint> {
+        let mut lints = Vec::new();
+        self.lint_operators_at(registry, &DataKeyPath::empty(), &mut lints);
+        lints.sort_by(|a, b| a.at.cmp(&b.at));
+        lints
+    }
+
+    fn lint_operators_at(&self, registry: &OperatorRegistry, at: &DataKeyPath, lints: &mut Vec<OperatorLint>) {
+        match self {
+            RawData::String(inner) if operators::looks_like_operator(inner) => {
+                if let Err(error) = operators::Expr::parse(inner) {
+                    lints.push(OperatorLint {
+                        at: at.clone(),
+                        text: inner.clone(),
+                        error,
+                        suggestion: suggest_operator_fix(inner, registry),
+                    });
+                }
+            },
+            RawData::Sequence(inner) => {
+                for (index, item) in inner.iter().enumerate() {
+                    item.lint_operators_at(registry, &at.child(index.to_string()), lints);
+                }
+            },
+            RawData::Mapping(inner) => {
+                for (inner_key, inner_value) in inner {
+                    inner_value.lint_operators_at(registry, &at.child(inner_key.clone()), lints);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Either the whole text corrected so that it parses, or only the operator name when that is not enough.
+fn suggest_operator_fix(text: &str, registry: &OperatorRegistry) -> Option<String> {
+    let mut candidate = text.to_owned();
+
+    let after_opening = text.find(operators::OPERATOR_OPENING)? + operators::OPERATOR_OPENING.len();
+    let name_start = after_opening + (text[after_opening..].len() - text[after_opening..].trim_start().len());
+    let name_end = text[name_start..].find(|c: char| !operators::is_operator_name_char(c))
+        .map_or(text.len(), |index| name_start + index);
+    let name = &text[name_start..name_end];
+
+    let suggested_name = match name.is_empty() || registry.contains(name) {
+        true => None,
+        false => registry.suggest(name),
+    };
+    if let Some(suggested_name) = suggested_name {
+        candidate.replace_range(name_start..name_end, suggested_name);
+    }
+
+    // References missing their `&`, one at a time.
+    loop {
+        match operators::Expr::parse(&candidate) {
+            Ok(_) if candidate != text => return Some(candidate),
+            Ok(_) => return None,
+            Err(error) => {
+                let preceded_by_whitespace = candidate[..error.span.start].ends_with(char::is_whitespace);
+                let starts_reference = candidate[error.span.start..].starts_with(operators::is_bare_reference_char);
+                if !preceded_by_whitespace || !starts_reference {
+                    break
+                }
+                candidate.insert(error.span.start, '&');
+            },
+        }
+    }
+
+    suggested_name.map(str::to_owned)
+}
+
+/// A string which looks like an operator, but is kept as a string since it cannot be parsed as one.
+#[derive(Debug, Clone)]
+pub struct OperatorLint {
+    pub at: DataKeyPath,
+    pub text: String,
+    pub error: operators::ExprSyntaxError,
+    pub suggestion: Option<String>,
+}
+
+impl Display for OperatorLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "at '{}': {} in what looks like an operator, kept as a string", self.at, self.error.message)?;
+        writeln!(f, "{}", operators::underline(&self.text, &self.error.span))?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "did you mean '{}'? ", suggestion)?;
+        }
+        write!(f, "Start it with '{}' to keep it as a string.", operators::ESCAPED_OPERATOR_OPENING)
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub enum RawOperatorData {
     #[default]
@@ -156,28 +321,45 @@ pub enum RawOperatorData {
     Mapping(HashMap<DataKey, RawOperatorData>)
 }
 
-impl TryInto<OperatorData> for RawOperatorData {
-    type Error = OperatorParsingError;
+impl RawOperatorData {
+    /// Same as [`RawData::recursive_merge`], for documents read from different files.
+    pub fn recursive_merge(self, other: RawOperatorData) -> RawOperatorData {
+        match (self, other) {
+            (RawOperatorData::Mapping(self_mapping), RawOperatorData::Mapping(other_mapping)) => {
+                RawOperatorData::Mapping(merge_mappings(self_mapping, other_mapping, RawOperatorData::recursive_merge))
+            },
+            (_, other) => other
+        }
+    }
 
-    fn try_into(self) -> Result<OperatorData, Self::Error> {
+    /// Parses operators as native ones, or as those registered in `registry`.
+    pub fn try_into_operator_data(self, registry: &OperatorRegistry) -> Result<OperatorData, Box<OperatorDataParsingError>> {
+        self.try_into_operator_data_at(registry, &DataKeyPath::empty())
+    }
+
+    fn try_into_operator_data_at(self, registry: &OperatorRegistry, at: &DataKeyPath) -> Result<OperatorData, Box<OperatorDataParsingError>> {
         match self {
             RawOperatorData::Null => Ok(OperatorData::Null),
             RawOperatorData::Boolean(inner) => Ok(OperatorData::Boolean(inner)),
             RawOperatorData::Number(inner) => Ok(OperatorData::Number(inner)),
             RawOperatorData::String(inner) => Ok(OperatorData::String(inner)),
-            RawOperatorData::RawOperator(inner) => crate::operators::native::NativeOperator::try_parsing_operator(&inner).map( |it| OperatorData::Operator(it)),
+            RawOperatorData::RawOperator(inner) => match registry.try_parsing_operator(&inner) {
+                Ok(operator) => Ok(OperatorData::Operator(operator)),
+                Err((source, reason)) => Err(Box::new(OperatorDataParsingError { at: at.clone(), expr: inner, source, reason })),
+            },
             RawOperatorData::Sequence(inner) => {
                 let mut sequence = Vec::with_capacity(inner.len());
-                for item in inner {
-                    let intoed = item.try_into()?;
+                for (index, item) in inner.into_iter().enumerate() {
+                    let intoed = item.try_into_operator_data_at(registry, &at.child(index.to_string()))?;
                     sequence.push(intoed);
                 }
                 Ok(OperatorData::Sequence(sequence))
             },
             RawOperatorData::Mapping(inner) => {
                 let mut mapping = HashMap::with_capacity(inner.len());
-                for (inner_key, inner_value) in inner {
-                    let intoed = inner_value.try_into()?;
+                // Merges are over by now.
+                for (inner_key, inner_value) in inner.into_iter().filter(|(key, _)| !operators::is_replace_marker(key.as_str())) {
+                    let intoed = inner_value.try_into_operator_data_at(registry, &at.child(inner_key.clone()))?;
                     mapping.insert(inner_key, intoed);
                 }
                 Ok(OperatorData::Mapping(mapping))
@@ -186,13 +368,131 @@ impl TryInto<OperatorData> for RawOperatorData {
     }
 }
 
+impl TryInto<OperatorData> for RawOperatorData {
+    type Error = Box<OperatorDataParsingError>;
+
+    fn try_into(self) -> Result<OperatorData, Self::Error> {
+        self.try_into_operator_data(&OperatorRegistry::new())
+    }
+}
+
+/// An operator which could not be parsed, pointing at the offending part of its expression.
+#[derive(Debug, Clone)]
+pub struct OperatorDataParsingError {
+    pub at: DataKeyPath,
+    pub expr: operators::Expr,
+    pub source: Option<OperatorSource>,
+    pub reason: OperatorParsingErrorReason,
+}
+
+impl OperatorDataParsingError {
+    /// The innermost call the error is about, and why it could not be parsed.
+    fn innermost(&self) -> (&operators::Expr, &OperatorParsingErrorReason) {
+        let (mut expr, mut reason) = (&self.expr, &self.reason);
+        while let OperatorParsingErrorReason::InNestedOperator { position, name, reason: inner_reason } = reason {
+            let nested = match expr.arguments.get(*position) {
+                Some(operators::Argument::Expr(inner)) => Some(inner.as_ref()),
+                // The first alternative with that name, which may not be the failing one if several are.
+                Some(operators::Argument::Fallback(alternatives)) => alternatives.iter().find_map(|alternative| match alternative {
+                    operators::Argument::Expr(inner) if inner.name.0 == *name => Some(inner.as_ref()),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            match nested {
+                Some(inner) => (expr, reason) = (inner, inner_reason),
+                None => break,
+            }
+        }
+        (expr, reason)
+    }
+
+    /// The part of the expression the error is about.
+    pub fn span(&self) -> operators::Span {
+        let (expr, reason) = self.innermost();
+        let location = &expr.location;
+        match reason {
+            OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position, .. }
+            | OperatorParsingErrorReason::InvalidArgument { position, .. } => location.arguments.get(*position).cloned(),
+            // From the first argument too many, to the last one.
+            OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { maximum: Some(maximum), found, .. } if found > maximum => {
+                location.arguments.get(*maximum)
+                    .zip(location.arguments.last())
+                    .map(|(first, last)| first.start..last.end)
+            },
+            // Right after the last argument given.
+            OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { .. } => {
+                let end = location.arguments.last().unwrap_or(&location.name).end;
+                Some(end..end)
+            },
+            _ => None,
+        }.unwrap_or(location.name.clone())
+    }
+}
+
+impl Display for OperatorDataParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at '{}': {}: {}", self.at, self.expr.name.0, self.reason)?;
+
+        let span = self.span();
+        if let (_, OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { .. }) = self.innermost() {
+            write!(f, " {}", self.expr.location.slice(&span))?;
+        }
+
+        write!(f, "\n{}", self.expr.location.underline(&span))
+    }
+}
+
+/// Field used to find an element of a sequence when a reference names it by key, as in `&jobs.web`.
+pub const DEFAULT_KEY_FIELD: &str = "name";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceError {
+    /// A relative reference went up more levels than the operator is deep.
+    AboveRoot { at: DataKeyPath, levels: usize },
+    NotFound(DataKeyPath),
+    /// Only mappings and sequences can be navigated into.
+    NotTraversable(DataKeyPath),
+    IndexOutOfBounds { at: DataKeyPath, index: isize },
+    /// No element of the sequence has `field` set to `value`.
+    NoMatch { at: DataKeyPath, field: String, value: String },
+    /// Several elements of the sequence have `field` set to `value`.
+    AmbiguousMatch { at: DataKeyPath, field: String, value: String, count: usize },
+    /// The referenced value still holds operators that have yet to be executed.
+    Unevaluated(DataKeyPath),
+    /// No enclosing operator binds the variable, as happens outside of a template being instantiated.
+    UnboundVariable(String),
+}
+
+impl Display for ReferenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceError::AboveRoot { at, levels } => write!(f, "cannot go up {} level(s) from '{}', which is {} level(s) deep", levels, at, at.len()),
+            ReferenceError::NotFound(path) => write!(f, "nothing found at '{}'", path),
+            ReferenceError::NotTraversable(path) => write!(f, "'{}' is neither a mapping nor a sequence", path),
+            ReferenceError::IndexOutOfBounds { at, index } => write!(f, "index {} is out of bounds for the sequence at '{}'", index, at),
+            ReferenceError::NoMatch { at, field, value } => write!(f, "no element of the sequence at '{}' has '{}' set to '{}'", at, field, value),
+            ReferenceError::AmbiguousMatch { at, field, value, count } => write!(f, "{} elements of the sequence at '{}' have '{}' set to '{}'", count, at, field, value),
+            ReferenceError::Unevaluated(path) => write!(f, "'{}' has not been evaluated", path),
+            ReferenceError::UnboundVariable(name) => write!(f, "variable '{}{}' is not bound", operators::VARIABLE_SIGIL, name),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct OperatorDataExecutionError {
-    operator: Operator,
-    at: DataKeyPath,
-    reason: OperatorExecutionErrorReason,
+    pub operator: Operator,
+    pub at: DataKeyPath,
+    pub reason: OperatorExecutionErrorReason,
 }
 
-pub type OperatorDataExecutionResult = Result<(), OperatorDataExecutionError>;
+impl Display for OperatorDataExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at '{}': {}", self.at, self.reason)
+    }
+}
+
+pub type OperatorDataExecutionResult = Result<(), Vec<OperatorDataExecutionError>>;
 
 #[derive(Default, Clone, Debug)]
 pub enum OperatorData {
@@ -206,35 +506,623 @@ pub enum OperatorData {
     Mapping(HashMap<DataKey, OperatorData>)
 }
 
+// Strings are kept as is, and never parsed as operators.
+/// Drops `(( replace-subtree ))` keys, as data read this way is never merged.
+impl From<RawData> for OperatorData {
+    fn from(value: RawData) -> Self {
+        match value {
+            RawData::Null => OperatorData::Null,
+            RawData::Boolean(inner) => OperatorData::Boolean(inner),
+            RawData::Number(inner) => OperatorData::Number(inner),
+            RawData::String(inner) => OperatorData::String(inner),
+            RawData::Sequence(inner) => OperatorData::Sequence(inner.into_iter().map(OperatorData::from).collect()),
+            RawData::Mapping(inner) => OperatorData::Mapping(
+                inner.into_iter()
+                    .filter(|(key, _)| !operators::is_replace_marker(key.as_str()))
+                    .map(|(key, value)| (key, OperatorData::from(value)))
+                    .collect()
+            ),
+        }
+    }
+}
+
+/// Fails on the first operator found, which should have been executed beforehand.
+impl TryFrom<OperatorData> for RawData {
+    type Error = Operator;
+
+    fn try_from(value: OperatorData) -> Result<Self, Self::Error> {
+        match value {
+            OperatorData::Null => Ok(RawData::Null),
+            OperatorData::Boolean(inner) => Ok(RawData::Boolean(inner)),
+            OperatorData::Number(inner) => Ok(RawData::Number(inner)),
+            OperatorData::String(inner) => Ok(RawData::String(inner)),
+            OperatorData::Operator(inner) => Err(inner),
+            OperatorData::Sequence(inner) => {
+                let mut sequence = Vec::with_capacity(inner.len());
+                for item in inner {
+                    sequence.push(item.try_into()?);
+                }
+                Ok(RawData::Sequence(sequence))
+            },
+            OperatorData::Mapping(inner) => {
+                let mut mapping = HashMap::with_capacity(inner.len());
+                for (inner_key, inner_value) in inner {
+                    mapping.insert(inner_key, inner_value.try_into()?);
+                }
+                Ok(RawData::Mapping(mapping))
+            },
+        }
+    }
+}
+
 impl OperatorData {
-    pub fn execute_operators(&mut self, path: DataKeyPath) -> OperatorDataExecutionResult {
+    fn child(&self, key: &DataKey) -> Option<&OperatorData> {
         match self {
-            OperatorData::Null => Ok(()),
-            OperatorData::Boolean(_) => Ok(()),
-            OperatorData::Number(_) => Ok(()),
-            OperatorData::String(_) => Ok(()),
-            OperatorData::Operator(inner) => {
-                let op = inner.clone();
-                match op.execute(self, &path) {
-                    Err(error) => {
-                        Err(OperatorDataExecutionError {
-                            operator: op,
-                            at: path,
-                            reason: error
-                        })
-                    }
-                    _ => Ok(()),
-                }
+            OperatorData::Mapping(inner) => inner.get(key),
+            OperatorData::Sequence(inner) => {
+                let index: usize = key.clone().try_into().ok()?;
+                inner.get(index)
+            },
+            _ => None,
+        }
+    }
 
+    fn child_mut(&mut self, key: &DataKey) -> Option<&mut OperatorData> {
+        match self {
+            OperatorData::Mapping(inner) => inner.get_mut(key),
+            OperatorData::Sequence(inner) => {
+                let index: usize = key.clone().try_into().ok()?;
+                inner.get_mut(index)
             },
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, path: &DataKeyPath) -> Option<&OperatorData> {
+        path.keys().iter().try_fold(self, |current, key| current.child(key))
+    }
+
+    pub fn get_mut(&mut self, path: &DataKeyPath) -> Option<&mut OperatorData> {
+        path.keys().iter().try_fold(self, |current, key| current.child_mut(key))
+    }
+
+    /// Same as [`RawData::recursive_merge`].
+    pub fn recursive_merge(self, other: OperatorData) -> OperatorData {
+        match (self, other) {
+            (OperatorData::Mapping(self_mapping), OperatorData::Mapping(other_mapping)) => {
+                OperatorData::Mapping(merge_mappings(self_mapping, other_mapping, OperatorData::recursive_merge))
+            },
+            (_, other) => other
+        }
+    }
+
+    pub fn contains_operators(&self) -> bool {
+        match self {
+            OperatorData::Operator(_) => true,
+            OperatorData::Sequence(inner) => inner.iter().any(OperatorData::contains_operators),
+            OperatorData::Mapping(inner) => inner.values().any(OperatorData::contains_operators),
+            _ => false,
+        }
+    }
+
+    /// Structural equality, where operators are never equal to anything.
+    pub fn deep_equals(&self, other: &OperatorData) -> bool {
+        match (self, other) {
+            (OperatorData::Null, OperatorData::Null) => true,
+            (OperatorData::Boolean(a), OperatorData::Boolean(b)) => a == b,
+            (OperatorData::Number(a), OperatorData::Number(b)) => a == b,
+            (OperatorData::String(a), OperatorData::String(b)) => a == b,
+            (OperatorData::Sequence(a), OperatorData::Sequence(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.deep_equals(b))
+            },
+            (OperatorData::Mapping(a), OperatorData::Mapping(b)) => {
+                a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| a.deep_equals(b)))
+            },
+            _ => false,
+        }
+    }
+
+    // Scalars are compared to `value` as they would be written in the reference.
+    pub(crate) fn matches_scalar(&self, value: &str) -> bool {
+        match self {
+            OperatorData::Null => value == "null",
+            OperatorData::Boolean(inner) => inner.to_string() == value,
+            OperatorData::Number(inner) => inner.to_string() == value,
+            OperatorData::String(inner) => inner == value,
+            _ => false,
+        }
+    }
+
+    /// Index of the only element of `sequence`, lying at `at`, which is a mapping whose `field` holds `value`.
+    fn find_by_field(sequence: &[OperatorData], field: &str, value: &str, at: &DataKeyPath) -> Result<usize, ReferenceError> {
+        let field_key = DataKey::from(field);
+        let mut found = vec![];
+
+        for (index, item) in sequence.iter().enumerate() {
+            let field_value = match item {
+                OperatorData::Operator(_) => return Err(ReferenceError::Unevaluated(at.child(index.to_string()))),
+                OperatorData::Mapping(inner) => inner.get(&field_key),
+                _ => None,
+            };
+
+            match field_value {
+                Some(OperatorData::Operator(_)) => {
+                    return Err(ReferenceError::Unevaluated(at.child(index.to_string()).child(field_key)))
+                },
+                Some(field_value) if field_value.matches_scalar(value) => found.push(index),
+                _ => {},
+            }
+        }
+
+        match found.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(ReferenceError::NoMatch { at: at.clone(), field: field.to_owned(), value: value.to_owned() }),
+            _ => Err(ReferenceError::AmbiguousMatch { at: at.clone(), field: field.to_owned(), value: value.to_owned(), count: found.len() }),
+        }
+    }
+
+    /// Follows `reference` as written by the operator at `at`, returning where it led and the value found there.
+    /// Variables can only be followed through an [`EvaluationContext`].
+    pub fn resolve(&self, reference: &Reference, at: &DataKeyPath) -> Result<(DataKeyPath, &OperatorData), ReferenceError> {
+        let (path, current) = self.locate(reference, at)?;
+        if current.contains_operators() {
+            return Err(ReferenceError::Unevaluated(path))
+        }
+        Ok((path, current))
+    }
+
+    /// Same as [`OperatorData::resolve`], but the value found may still hold operators, as templates do.
+    pub fn locate(&self, reference: &Reference, at: &DataKeyPath) -> Result<(DataKeyPath, &OperatorData), ReferenceError> {
+        let path = match &reference.anchor {
+            ReferenceAnchor::Root => DataKeyPath::empty(),
+            ReferenceAnchor::Relative(levels) => at.ancestor(*levels)
+                .ok_or(ReferenceError::AboveRoot { at: at.clone(), levels: *levels })?,
+            ReferenceAnchor::Variable(name) => return Err(ReferenceError::UnboundVariable(name.clone())),
+        };
+
+        self.get(&path)
+            .ok_or(ReferenceError::NotFound(path.clone()))?
+            .follow(path, &reference.segments)
+    }
+
+    /// Follows `segments` from this value, which lies at `path`.
+    /// Fails on operators met on the way, but not on those held by the value found.
+    pub(crate) fn follow(&self, mut path: DataKeyPath, segments: &[ReferenceSegment]) -> Result<(DataKeyPath, &OperatorData), ReferenceError> {
+        let mut current = self;
+        for segment in segments {
+            if let OperatorData::Operator(_) = current {
+                return Err(ReferenceError::Unevaluated(path))
+            }
+
+            let key: DataKey = match (current, segment) {
+                (OperatorData::Mapping(_), ReferenceSegment::Key(inner)) => inner.into(),
+                (OperatorData::Sequence(inner), ReferenceSegment::Key(key)) => match key.parse::<usize>() {
+                    Ok(_) => key.into(),
+                    Err(_) => OperatorData::find_by_field(inner, DEFAULT_KEY_FIELD, key, &path)?.to_string().into(),
+                },
+                (OperatorData::Sequence(inner), ReferenceSegment::Match { field, value }) => {
+                    OperatorData::find_by_field(inner, field, value, &path)?.to_string().into()
+                },
+                (OperatorData::Sequence(inner), ReferenceSegment::Index(index)) => {
+                    let normalized = if *index < 0 { inner.len() as isize + index } else { *index };
+                    if normalized < 0 || normalized as usize >= inner.len() {
+                        return Err(ReferenceError::IndexOutOfBounds { at: path, index: *index })
+                    }
+                    normalized.to_string().into()
+                },
+                _ => return Err(ReferenceError::NotTraversable(path)),
+            };
+
+            path = path.child(key.clone());
+            current = current.child(&key)
+                .ok_or(ReferenceError::NotFound(path.clone()))?;
+        }
+
+        Ok((path, current))
+    }
+
+    /// Subtrees at `excluded` paths are skipped.
+    pub(crate) fn collect_operators(&self, path: DataKeyPath, excluded: &[DataKeyPath], operators: &mut Vec<(DataKeyPath, Operator)>) {
+        if excluded.contains(&path) {
+            return
+        }
+        match self {
+            OperatorData::Operator(inner) => operators.push((path, inner.clone())),
             OperatorData::Sequence(inner) => {
-                // The length of the current list could change under our feet.
-                // Keep the implementation deterministic, but allow it to be weird.
-                unimplemented!()
+                for (index, item) in inner.iter().enumerate() {
+                    item.collect_operators(path.child(index.to_string()), excluded, operators);
+                }
             },
             OperatorData::Mapping(inner) => {
-                unimplemented!()
+                for (key, value) in inner {
+                    value.collect_operators(path.child(key.clone()), excluded, operators);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Where the templates referenced by operators lie, as found before anything is executed.
+    /// Those holding the operator referencing them are not templates, as it would then never be executed.
+    fn collect_templates(&self) -> Vec<DataKeyPath> {
+        let mut operators = vec![];
+        self.collect_operators(DataKeyPath::empty(), &[], &mut operators);
+
+        let mut templates = vec![];
+        for (path, operator) in operators {
+            for reference in operator.templates() {
+                // Templates picked through variables, or not found, are reported when executing the operator.
+                if let Ok((template, _)) = self.locate(&reference, &path) && !path.starts_with(&template) {
+                    templates.push(template);
+                }
+            }
+        }
+        templates
+    }
+
+    // Written back as they were, as `defer` does, so that the template can be output.
+    fn write_back_operators(&mut self) {
+        match self {
+            OperatorData::Operator(inner) => *self = OperatorData::String(inner.expr().to_string()),
+            OperatorData::Sequence(inner) => inner.iter_mut().for_each(OperatorData::write_back_operators),
+            OperatorData::Mapping(inner) => inner.values_mut().for_each(OperatorData::write_back_operators),
+            _ => {},
+        }
+    }
+
+    /// Applies what executing the operator at `path` did.
+    pub(crate) fn apply_effect(&mut self, path: &DataKeyPath, effect: OperatorEffect) -> Result<(), OperatorExecutionErrorReason> {
+        match effect {
+            OperatorEffect::Replace(value) => {
+                let node = self.get_mut(path)
+                    .ok_or_else(|| OperatorExecutionErrorReason::OtherError(format!("cannot replace '{}', which no longer exists", path)))?;
+                *node = value;
+                Ok(())
+            },
+            OperatorEffect::MergeIntoParent(merged) => {
+                let not_in_mapping = || OperatorExecutionErrorReason::OtherError("can only be the value of a key in a mapping".to_owned());
+                let (key, parent) = path.keys().last()
+                    .zip(path.ancestor(1))
+                    .ok_or_else(not_in_mapping)?;
+                match self.get_mut(&parent) {
+                    Some(OperatorData::Mapping(inner)) => {
+                        inner.remove(key);
+                        *inner = merge_mappings(merged, std::mem::take(inner), OperatorData::recursive_merge);
+                        Ok(())
+                    },
+                    _ => Err(not_in_mapping()),
+                }
             },
         }
     }
+
+    /// Executes every operator of the document, rank after rank.
+    /// Operators referencing values that have yet to be evaluated are retried once other operators made progress,
+    /// which can carry them over to later ranks.
+    /// Templates are left out, and kept as they were written.
+    pub fn execute_operators(&mut self) -> OperatorDataExecutionResult {
+        let templates = self.collect_templates();
+        let mut operators = vec![];
+        self.collect_operators(DataKeyPath::empty(), &templates, &mut operators);
+        let errors = self.execute_collected(operators.into_iter().map(|(path, operator)| (path, operator, Bindings::default())).collect());
+
+        for template in &templates {
+            if let Some(node) = self.get_mut(template) {
+                node.write_back_operators();
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Executes `operators`, lying in this document with the variables bound for each, as `execute_operators` does.
+    /// Errors are sorted by path.
+    pub(crate) fn execute_collected(&mut self, mut operators: Vec<(DataKeyPath, Operator, Bindings)>) -> Vec<OperatorDataExecutionError> {
+        operators.sort_by(|(a_path, a, _), (b_path, b, _)| a.priority().cmp(&b.priority()).then(a_path.cmp(b_path)));
+
+        let mut errors = vec![];
+        // Operators referencing values yet to be evaluated, along with the last reason they could not be executed.
+        let mut waiting: Vec<(OperatorDataExecutionError, Bindings)> = vec![];
+        let mut operators = operators.into_iter().peekable();
+
+        while let Some((_, next, _)) = operators.peek() {
+            let rank = next.priority();
+            let mut pending: Vec<_> = waiting.drain(..).map(|(error, bindings)| (error.at, error.operator, bindings)).collect();
+            while let Some(operator) = operators.next_if(|(_, operator, _)| operator.priority() == rank) {
+                pending.push(operator);
+            }
+
+            loop {
+                let mut has_progressed = false;
+
+                for (path, operator, bindings) in pending {
+                    let context = EvaluationContext { root: self, path: path.clone(), bindings: bindings.clone() };
+                    match operator.apply(&context).and_then(|effect| self.apply_effect(&path, effect)) {
+                        Ok(()) => has_progressed = true,
+                        Err(reason @ OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::Unevaluated(_))) => {
+                            waiting.push((OperatorDataExecutionError { operator, at: path, reason }, bindings));
+                        },
+                        Err(reason) => errors.push(OperatorDataExecutionError { operator, at: path, reason }),
+                    }
+                }
+
+                if !has_progressed {
+                    break;
+                }
+                pending = waiting.drain(..).map(|(error, bindings)| (error.at, error.operator, bindings)).collect();
+            }
+        }
+
+        // Whatever is left depends on operators that failed, or on each other.
+        errors.extend(waiting.into_iter().map(|(error, _)| error));
+        errors.sort_by(|a, b| a.at.cmp(&b.at));
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::{operators::OperatorExecutionErrorReason, test_helpers::*};
+
+    #[test]
+    fn test_grab_relative_references() {
+        let mut data = document("
+service:
+  port: 8080
+  name: web
+  url: (( grab &.port ))
+  nested:
+    port: (( grab &..port ))
+    also: (( grab &^2.port ))
+    list:
+      - 1
+      - 2
+      - (( grab &.[0] ))
+top: (( grab &.service.name ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(number_at(&data, &["service", "url"]), 8080.0);
+        assert_eq!(number_at(&data, &["service", "nested", "port"]), 8080.0);
+        assert_eq!(number_at(&data, &["service", "nested", "also"]), 8080.0);
+        assert_eq!(number_at(&data, &["service", "nested", "list", "2"]), 1.0);
+        assert!(matches!(data.get(&path(&["top"])), Some(OperatorData::String(inner)) if inner == "web"));
+    }
+
+    #[test]
+    fn test_grab_above_root() {
+        let mut data = document("
+a:
+  b: (( grab &...c ))
+c: 1
+d: (( grab &^2.c ))
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            &errors[0],
+            (at, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::AboveRoot { levels: 3, .. })) if *at == path(&["a", "b"])
+        ));
+        assert!(matches!(
+            &errors[1],
+            (at, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::AboveRoot { levels: 2, .. })) if *at == path(&["d"])
+        ));
+        assert_eq!(
+            ReferenceError::AboveRoot { at: path(&["d"]), levels: 2 }.to_string(),
+            "cannot go up 2 level(s) from 'd', which is 1 level(s) deep"
+        );
+    }
+
+    #[test]
+    fn test_grab_indices_and_chains() {
+        let mut data = document("
+list: [10, 20, 30]
+last: (( grab &list[-1] ))
+first: (( grab &list.[0] ))
+chained: (( grab &last ))
+out_of_bounds: (( grab &list[3] ))
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(number_at(&data, &["last"]), 30.0);
+        assert_eq!(number_at(&data, &["first"]), 10.0);
+        assert_eq!(number_at(&data, &["chained"]), 30.0);
+        assert!(matches!(
+            errors.as_slice(),
+            [(_, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::IndexOutOfBounds { index: 3, .. }))]
+        ));
+    }
+
+    #[test]
+    fn test_grab_by_field_match() {
+        let mut data = document("
+jobs:
+  - name: web
+    instances: 3
+  - name: worker
+    id: 7
+    instances: 5
+  - name: worker2
+    id: 7
+    instances: 1
+  - just a string
+explicit: (( grab &jobs.[name=web].instances ))
+implicit: (( grab &jobs.worker.instances ))
+by_number: (( grab &jobs[id=7] ))
+by_index: (( grab &jobs.2.instances ))
+missing: (( grab &jobs.db.instances ))
+ambiguous: (( grab &jobs[id=7].instances ))
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(number_at(&data, &["explicit"]), 3.0);
+        assert_eq!(number_at(&data, &["implicit"]), 5.0);
+        assert_eq!(number_at(&data, &["by_index"]), 1.0);
+        assert!(matches!(data.get(&path(&["by_number"])), Some(OperatorData::Operator(_))));
+
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(
+            &errors[0],
+            (_, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::AmbiguousMatch { count: 2, .. }))
+        ));
+        assert!(matches!(
+            &errors[1],
+            (_, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::AmbiguousMatch { count: 2, .. }))
+        ));
+        assert!(matches!(
+            &errors[2],
+            (_, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::NoMatch { field, value, .. })) if field == "name" && value == "db"
+        ));
+    }
+
+    #[test]
+    fn test_grab_by_field_match_waits_for_operators() {
+        let mut data = document("
+names:
+  web: frontend
+jobs:
+  - name: (( grab &names.web ))
+    instances: 2
+instances: (( grab &jobs.frontend.instances ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(number_at(&data, &["instances"]), 2.0);
+    }
+
+    #[test]
+    fn test_grab_cycle() {
+        let mut data = document("
+a: (( grab &b ))
+b: (( grab &a ))
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|(_, reason)| matches!(
+            reason,
+            OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::Unevaluated(_))
+        )));
+    }
+
+    #[test]
+    fn test_replace_missing_node() {
+        let mut data = document("a: 1");
+
+        assert!(data.apply_effect(&path(&["a"]), OperatorEffect::Replace(OperatorData::Number(2.0))).is_ok());
+        assert_eq!(number_at(&data, &["a"]), 2.0);
+        assert!(data.apply_effect(&path(&["b"]), OperatorEffect::Replace(OperatorData::Null)).is_err());
+    }
+
+    #[test]
+    fn test_grab_fallback() {
+        let mut data = document("
+a: 1
+b: (( grab &missing || &a ))
+c: (( grab &missing || &d || \"default\" ))
+d: (( grab &nowhere || &b ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(number_at(&data, &["b"]), 1.0);
+        assert_eq!(number_at(&data, &["c"]), 1.0);
+        assert_eq!(number_at(&data, &["d"]), 1.0);
+
+        let mut data = document("a: (( grab &b || &c.d ))");
+        let errors = data.execute_operators().unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "at 'a': no alternative available, 1) reference unavailable, nothing found at 'b', 2) reference unavailable, nothing found at 'c'"
+        );
+    }
+
+    #[test]
+    fn test_replace_marker() {
+        let base = raw_document("resources: {cpu: 1, memory: 512}");
+        let overrides = raw_document("
+resources:
+  (( replace-subtree )):
+  memory: 1024
+new:
+  ((  replace-subtree  )): ~
+  a: 1
+renamed: (( replace \"a-b\" \"-\" \"_\" ))
+");
+        let mut data: OperatorData = base.recursive_merge(overrides).into_raw_operator_data().try_into().unwrap();
+        data.execute_operators().unwrap();
+
+        // The marker is not mistaken for the `replace` operator.
+        assert_eq!(yaml_at(&data, &[]), "new:\n  a: 1\nrenamed: a_b\nresources:\n  memory: 1024\n");
+    }
+
+    #[test]
+    fn test_parsing_errors() {
+        assert_eq!(
+            parsing_error("a:\n  b: (( grab \"foo\" ))"),
+            "at 'a.b': grab: argument 1 expected reference or fallback chain, found string literal \"foo\"\n(( grab \"foo\" ))\n        ^^^^^"
+        );
+        assert_eq!(
+            parsing_error("a:\n  - (( param 1 0x2 ))"),
+            "at 'a.0': param: expected 0 arguments, found 2\n(( param 1 0x2 ))\n         ^^^^^"
+        );
+        assert_eq!(
+            parsing_error("a: (( grab ))"),
+            "at 'a': grab: expected 1 argument, found 0\n(( grab ))\n       ^"
+        );
+        assert_eq!(
+            parsing_error("a: (( nope &b ))"),
+            "at 'a': nope: unknown operator\n(( nope &b ))\n   ^^^^"
+        );
+        assert_eq!(
+            parsing_error("a: (( grabb &b ))"),
+            "at 'a': grabb: unknown operator, did you mean 'grab'?\n(( grabb &b ))\n   ^^^^^"
+        );
+    }
+
+    fn lints(yaml: &str) -> Vec<OperatorLint> {
+        let raw = crate::raw::yaml::parse_as_raw_data(&mut BufReader::new(yaml.as_bytes())).unwrap();
+        raw.lint_operators(&OperatorRegistry::new())
+    }
+
+    #[test]
+    fn test_operator_lints() {
+        let found = lints("
+a: (( grab a.b ))
+b: (( grabb &a ))
+c:
+  - (( grabb a c.d ))
+  - ((grab &a
+d: (( grab &a ))
+e: not (( an operator ))
+f: \\(( kept as is ))
+");
+        let summary: Vec<_> = found.iter()
+            .map(|lint| (lint.at.to_string(), lint.error.message.as_str(), lint.suggestion.as_deref()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("a".to_owned(), "unexpected 'a'", Some("(( grab &a.b ))")),
+            ("c.0".to_owned(), "unexpected 'a'", Some("(( grab &a &c.d ))")),
+            ("c.1".to_owned(), "unexpected end of operator", None),
+        ]);
+
+        assert_eq!(
+            found[0].to_string(),
+            "at 'a': unexpected 'a' in what looks like an operator, kept as a string\n(( grab a.b ))\n        ^\ndid you mean '(( grab &a.b ))'? Start it with '\\((' to keep it as a string."
+        );
+    }
+
+    #[test]
+    fn test_escaped_operator() {
+        let data = document("a: \\(( grab &b ))\nb: '  \\((x'");
+        assert!(matches!(data.get(&path(&["a"])), Some(OperatorData::String(inner)) if inner == "(( grab &b ))"));
+        assert!(matches!(data.get(&path(&["b"])), Some(OperatorData::String(inner)) if inner == "  ((x"));
+    }
 }
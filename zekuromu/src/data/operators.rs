@@ -1,14 +1,150 @@
+use std::{fmt::Display, num::IntErrorKind, ops::Range, path::Path, rc::Rc};
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Expr {
     pub name: OperatorName,
     pub arguments: Vec<Argument>,
+    pub location: ExprLocation,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExprSyntaxError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ExprSyntaxError {
+    /// Points at the first character which is not whitespace from `start`.
+    fn at(input: &str, start: usize) -> ExprSyntaxError {
+        let start = start.min(input.len());
+        let skipped = input[start..].len() - input[start..].trim_start().len();
+        match input[start + skipped..].chars().next() {
+            Some(c) => ExprSyntaxError {
+                span: start + skipped..start + skipped + c.len_utf8(),
+                message: format!("unexpected '{}'", c),
+            },
+            None => ExprSyntaxError {
+                span: input.len()..input.len(),
+                message: "unexpected end of operator".to_owned(),
+            },
+        }
+    }
+}
+
+/// Strings starting with this, ignoring leading whitespace, are meant as operators.
+pub const OPERATOR_OPENING: &str = "((";
+/// Written instead of `((` for a string which should be kept as is.
+pub const ESCAPED_OPERATOR_OPENING: &str = "\\((";
+/// A mapping holding this as a key replaces what it is merged over, instead of being merged into it.
+/// Never the name of an operator, see [`crate::operators::registry::OperatorRegistry::register`].
+pub const REPLACE_MARKER: &str = "replace-subtree";
+
+/// Whether `key` is `(( replace-subtree ))`, however spaced.
+pub fn is_replace_marker(key: &str) -> bool {
+    looks_like_operator(key)
+        && Expr::try_parse(key).is_some_and(|expr| expr.name.0 == REPLACE_MARKER && expr.arguments.is_empty())
+}
+
+/// Starts the name of a variable in a reference, as in `&$item.name`.
+pub const VARIABLE_SIGIL: char = '$';
+
+/// `(( defer grab &a ))` takes the rest of the call as is, without parentheses.
+pub const DEFER_KEYWORD: &str = "defer";
+
+pub fn looks_like_operator(text: &str) -> bool {
+    text.trim_start().starts_with(OPERATOR_OPENING)
+}
+
+/// `\((` at the start of `text` is turned into `((`.
+pub fn unescape_operator_opening(text: &str) -> Option<String> {
+    let trimmed = text.trim_start();
+    trimmed.strip_prefix(ESCAPED_OPERATOR_OPENING)
+        .map(|rest| format!("{}{}{}", &text[..text.len() - trimmed.len()], OPERATOR_OPENING, rest))
+}
+
+/// The line of `text` holding the start of `span`, and a line of carets under `span`.
+pub fn underline(text: &str, span: &Span) -> String {
+    let start = span.start.min(text.len());
+    let line_start = text[..start].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = text[start..].find('\n').map_or(text.len(), |index| start + index);
+    let end = span.end.clamp(start, line_end);
+
+    let column = text[line_start..start].chars().count();
+    let width = text[start..end].chars().count().max(1);
+
+    format!("{}\n{}{}", &text[line_start..line_end], " ".repeat(column), "^".repeat(width))
+}
+
+/// Byte offsets into the text an expression was parsed from.
+pub type Span = Range<usize>;
+
+/// Where an expression and its parts were written, to point at them in errors.
+#[derive(Debug, Clone, Default)]
+pub struct ExprLocation {
+    pub text: Rc<str>,
+    pub name: Span,
+    /// One per argument.
+    pub arguments: Vec<Span>,
+    /// The file the expression was read from, if any, for paths relative to it.
+    pub origin: Option<Rc<Path>>,
+}
+
+// Two expressions are the same regardless of where they were written.
+impl PartialEq for ExprLocation {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl ExprLocation {
+    pub fn underline(&self, span: &Span) -> String {
+        underline(&self.text, span)
+    }
+
+    /// The text of `span`.
+    pub fn slice(&self, span: &Span) -> &str {
+        self.text.get(span.clone()).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Argument {
     StringLiteral(StringLiteral),
     NumberLiteral(NumberLiteral),
+    BooleanLiteral(BooleanLiteral),
     Reference(Reference),
+    /// A nested call, as in `(( concat (grab &a) "-" ))`, evaluated before the one holding it.
+    Expr(Box<Expr>),
+    /// Alternatives separated by `||`, as in `&a || &b || "default"`, the first one available being used.
+    Fallback(Vec<Argument>),
+    /// A bare word, as in `(( empty hash ))`.
+    Keyword(Keyword),
+}
+
+/// The only bare words arguments can be, others being taken as references missing their `&`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Keyword {
+    Hash,
+    Array,
+    String,
+    Yaml,
+    Json,
+    By,
+}
+
+impl Keyword {
+    pub const ALL: &'static [Keyword] = &[Keyword::Hash, Keyword::Array, Keyword::String, Keyword::Yaml, Keyword::Json, Keyword::By];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Hash => "hash",
+            Keyword::Array => "array",
+            Keyword::String => "string",
+            Keyword::Yaml => "yaml",
+            Keyword::Json => "json",
+            Keyword::By => "by",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -19,16 +155,266 @@ pub enum NumberLiteral {
     Bin(String),
 }
 
+/// Raised when a `NumberLiteral` cannot be evaluated into a number.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NumberLiteralError {
+    /// The digits are not valid for the literal's radix.
+    Invalid,
+    /// The value does not fit in a number without losing precision.
+    Overflow,
+}
+
+impl Display for NumberLiteralError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberLiteralError::Invalid => write!(f, "its digits are not valid"),
+            NumberLiteralError::Overflow => write!(f, "it does not fit in a number without losing precision"),
+        }
+    }
+}
+
+/// Integers above 2^53 cannot all be represented by a `f64`.
+const MAX_EXACT_INTEGER: u64 = 1 << 53;
+
+/// `value` as an integer, if it is one that a `f64` represents exactly.
+pub(crate) fn as_exact_integer(value: f64) -> Option<i64> {
+    (value.fract() == 0.0 && value.abs() <= MAX_EXACT_INTEGER as f64).then_some(value as i64)
+}
+
+impl NumberLiteral {
+    fn negated(self) -> NumberLiteral {
+        match self {
+            NumberLiteral::Float(inner) => NumberLiteral::Float(format!("-{}", inner)),
+            NumberLiteral::Hex(inner) => NumberLiteral::Hex(format!("-{}", inner)),
+            NumberLiteral::Oct(inner) => NumberLiteral::Oct(format!("-{}", inner)),
+            NumberLiteral::Bin(inner) => NumberLiteral::Bin(format!("-{}", inner)),
+        }
+    }
+
+    fn evaluate_integer(digits: &str, radix: u32) -> Result<f64, NumberLiteralError> {
+        let (is_negative, digits) = match digits.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, digits),
+        };
+
+        let magnitude = u64::from_str_radix(digits, radix)
+            .map_err(|error| match error.kind() {
+                IntErrorKind::PosOverflow => NumberLiteralError::Overflow,
+                _ => NumberLiteralError::Invalid,
+            })?;
+
+        if magnitude > MAX_EXACT_INTEGER {
+            return Err(NumberLiteralError::Overflow)
+        }
+
+        let value = magnitude as f64;
+        Ok(if is_negative { -value } else { value })
+    }
+}
+
+impl TryFrom<&NumberLiteral> for f64 {
+    type Error = NumberLiteralError;
+
+    fn try_from(value: &NumberLiteral) -> Result<Self, Self::Error> {
+        match value {
+            NumberLiteral::Float(inner) => {
+                let parsed: f64 = inner.parse()
+                    .map_err(|_| NumberLiteralError::Invalid)?;
+
+                if parsed.is_nan() {
+                    Err(NumberLiteralError::Invalid)
+                } else if parsed.is_infinite() {
+                    Err(NumberLiteralError::Overflow)
+                } else {
+                    Ok(parsed)
+                }
+            },
+            NumberLiteral::Hex(inner) => NumberLiteral::evaluate_integer(inner, 16),
+            NumberLiteral::Oct(inner) => NumberLiteral::evaluate_integer(inner, 8),
+            NumberLiteral::Bin(inner) => NumberLiteral::evaluate_integer(inner, 2),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct OperatorName(pub String);
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct StringLiteral(pub String);
 
+/// `true` or `false`.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Reference(pub Vec<String>);
+pub struct BooleanLiteral(pub bool);
 
-impl Reference {
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReferenceSegment {
+    /// On a sequence, a key which is not an index matches the element whose `name` is that key.
+    Key(String),
+    /// Negative indices count from the end of the sequence.
+    Index(isize),
+    /// The only element of a sequence whose `field` holds `value`.
+    Match { field: String, value: String },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReferenceAnchor {
+    /// From the root of the document.
+    Root,
+    /// Up a number of levels from the operator's own path,
+    /// `1` being the mapping or sequence holding the operator.
+    Relative(usize),
+    /// A variable bound by an enclosing operator, as in `&$item`, the name being written without `$`.
+    Variable(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reference {
+    pub anchor: ReferenceAnchor,
+    pub segments: Vec<ReferenceSegment>,
+}
+
+// What follows writes expressions back in their canonical form, which parses into the same expression.
+
+/// Quoted with `"`, escaping what needs to be.
+pub(crate) fn write_quoted(f: &mut std::fmt::Formatter<'_>, text: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// Bare when possible, quoted otherwise.
+fn write_reference_key(f: &mut std::fmt::Formatter<'_>, key: &str) -> std::fmt::Result {
+    if !key.is_empty() && key.chars().all(is_bare_reference_char) {
+        write!(f, "{}", key)
+    } else {
+        write_quoted(f, key)
+    }
+}
+
+impl Display for StringLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_quoted(f, &self.0)
+    }
+}
+
+impl Display for BooleanLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Display for NumberLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (prefix, digits) = match self {
+            NumberLiteral::Float(inner) => return write!(f, "{}", inner),
+            NumberLiteral::Hex(inner) => ("0x", inner),
+            NumberLiteral::Oct(inner) => ("0o", inner),
+            NumberLiteral::Bin(inner) => ("0b", inner),
+        };
+        match digits.strip_prefix('-') {
+            Some(digits) => write!(f, "-{}{}", prefix, digits),
+            None => write!(f, "{}{}", prefix, digits),
+        }
+    }
+}
+
+impl Display for ReferenceSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceSegment::Key(key) => write_reference_key(f, key),
+            ReferenceSegment::Index(index) => write!(f, "[{}]", index),
+            ReferenceSegment::Match { field, value } => {
+                write!(f, "[")?;
+                write_reference_key(f, field)?;
+                write!(f, "=")?;
+                write_reference_key(f, value)?;
+                write!(f, "]")
+            },
+        }
+    }
+}
+
+/// Relative references are written with dots, as in `&..a`.
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "&")?;
+        match &self.anchor {
+            ReferenceAnchor::Root => {},
+            ReferenceAnchor::Relative(levels) => write!(f, "{}", ".".repeat(*levels))?,
+            ReferenceAnchor::Variable(name) => write!(f, "{}{}", VARIABLE_SIGIL, name)?,
+        }
+        let is_variable = matches!(self.anchor, ReferenceAnchor::Variable(_));
+        for (index, segment) in self.segments.iter().enumerate() {
+            if (index > 0 || is_variable) && matches!(segment, ReferenceSegment::Key(_)) {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Argument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Argument::StringLiteral(inner) => write!(f, "{}", inner),
+            Argument::NumberLiteral(inner) => write!(f, "{}", inner),
+            Argument::BooleanLiteral(inner) => write!(f, "{}", inner),
+            Argument::Reference(inner) => write!(f, "{}", inner),
+            Argument::Expr(inner) => write!(f, "({})", Call(inner)),
+            Argument::Keyword(inner) => write!(f, "{}", inner.as_str()),
+            Argument::Fallback(alternatives) => {
+                for (index, alternative) in alternatives.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " || ")?;
+                    }
+                    write!(f, "{}", alternative)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+// A call without its surrounding parentheses.
+struct Call<'a>(&'a Expr);
+
+impl Display for Call<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Call(expr) = self;
+        write!(f, "{}", expr.name.0)?;
+        match &expr.arguments[..] {
+            [Argument::Expr(inner)] if expr.name.0 == DEFER_KEYWORD => write!(f, " {}", Call(inner)),
+            arguments => arguments.iter().try_for_each(|argument| write!(f, " {}", argument)),
+        }
+    }
+}
+
+/// Written as `(( name arguments... ))`, whatever the original spacing and quoting were.
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ))", OPERATOR_OPENING, Call(self))
+    }
+}
+
+/// Characters an operator name can hold after its first letter, as in `base64-decode`.
+pub(crate) fn is_operator_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+/// Characters a reference key can hold without being quoted or escaped.
+pub(crate) fn is_bare_reference_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
 }
 
 pub mod parser {
@@ -36,31 +422,70 @@ pub mod parser {
 
     use chumsky::{prelude::*, text::whitespace};
 
+    type Extra<'src> = extra::Err<Rich<'src, char>>;
+
     impl Expr {
         pub fn try_parse(input: &str) -> Option<Expr> {
-            let result = expr_parser().parse(input).into_result();
+            Expr::parse(input).ok()
+        }
+
+        /// Only the first syntax error is kept.
+        pub fn parse(input: &str) -> Result<Expr, ExprSyntaxError> {
+            let mut expr = expr_parser().parse(input)
+                .into_result()
+                .map_err(|errors| {
+                    let start = errors.first().map_or(0, |error| error.span().start);
+                    ExprSyntaxError::at(input, start)
+                })?;
+            expr.set_text(Rc::from(input));
+            Ok(expr)
+        }
+
+        /// Nested calls are read from the same file.
+        pub fn set_origin(&mut self, origin: Rc<Path>) {
+            for argument in &mut self.arguments {
+                argument.for_each_nested_expr(&mut |inner| inner.set_origin(origin.clone()));
+            }
+            self.location.origin = Some(origin);
+        }
 
-            match result {
-                Ok(expr) => Some(expr),
-                Err(_) => None, 
+        // Nested calls share the text of the outermost one, their spans being relative to it.
+        fn set_text(&mut self, text: Rc<str>) {
+            for argument in &mut self.arguments {
+                argument.set_text(&text);
+            }
+            self.location.text = text;
+        }
+    }
+
+    impl Argument {
+        fn set_text(&mut self, text: &Rc<str>) {
+            self.for_each_nested_expr(&mut |inner| inner.set_text(text.clone()));
+        }
+
+        fn for_each_nested_expr(&mut self, f: &mut impl FnMut(&mut Expr)) {
+            match self {
+                Argument::Expr(inner) => f(inner),
+                Argument::Fallback(alternatives) => alternatives.iter_mut().for_each(|alternative| alternative.for_each_nested_expr(f)),
+                _ => {},
             }
         }
     }
 
     // TODO: Handle much more than just ascii alphanumeric
-    fn at_least_x_alphanumeric_parser<'src>(amount: usize) -> impl Parser<'src, &'src str, Vec<char>> {
+    fn at_least_x_name_chars_parser<'src>(amount: usize) -> impl Parser<'src, &'src str, Vec<char>, Extra<'src>> {
         any()
-            .filter(char::is_ascii_alphanumeric)
+            .filter(|c| is_operator_name_char(*c))
             .repeated().at_least(amount)
             .collect::<Vec<char>>()
     }
 
-    fn operator_parser<'src>() -> impl Parser<'src, &'src str, OperatorName> {
+    fn operator_parser<'src>() -> impl Parser<'src, &'src str, OperatorName, Extra<'src>> {
         let one_alpha = any()
             .filter(char::is_ascii_alphabetic);
 
         one_alpha
-            .then(at_least_x_alphanumeric_parser(0))
+            .then(at_least_x_name_chars_parser(0))
             .map(|(start, rest)| {
                 let mut val = rest.clone();
                 val.insert(0, start);
@@ -68,156 +493,411 @@ pub mod parser {
             })
     }
 
-    fn hex_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    const DEC_DIGITS: &str = "0123456789";
+    const HEX_DIGITS: &str = "0123456789abcdefABCDEF";
+    const OCT_DIGITS: &str = "01234567";
+    const BIN_DIGITS: &str = "01";
+
+    // Digits can be separated by `_`, which carry no meaning and are dropped.
+    // Underscores only ever separate two digits.
+    fn digits_parser<'src>(digits: &'static str) -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        one_of(digits)
+            .then(
+                just('_').or_not()
+                    .ignore_then(one_of(digits))
+                    .repeated()
+                    .collect::<String>()
+            )
+            .map(|(first, rest)| {
+                let mut res = String::from(first);
+                res.push_str(&rest);
+                res
+            })
+    }
+
+    fn hex_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         just("0")
             .ignore_then(one_of("xX"))
             .ignore_then(
-                any()
-                    .filter(char::is_ascii_hexdigit)
-                    .repeated().at_least(1)
-                    .collect::<String>()
-                    .map(|v| String::from(v.to_lowercase()))
+                digits_parser(HEX_DIGITS)
+                    .map(|v| v.to_lowercase())
             )
             .map(NumberLiteral::Hex)
     }
 
-    fn oct_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    fn oct_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         just("0")
             .ignore_then(one_of("oO"))
-            .ignore_then(
-                one_of("01234567")
-                    .repeated()
-                    .at_least(1)
-                    .collect::<String>()
-            )
+            .ignore_then(digits_parser(OCT_DIGITS))
             .map(NumberLiteral::Oct)
     }
 
-    fn bin_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    fn bin_integer_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         just("0")
             .ignore_then(one_of("bB"))
-            .ignore_then(
-                one_of("01")
-                    .repeated()
-                    .at_least(1)
-                    .collect::<String>()
-            )
+            .ignore_then(digits_parser(BIN_DIGITS))
             .map(NumberLiteral::Bin)
     }
 
-    fn dec_parser<'src>() -> impl Parser<'src, &'src str, String> {
-        one_of("0123456789")
-            .repeated().at_least(1)
-            .collect::<String>()
+    fn dec_parser<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        digits_parser(DEC_DIGITS)
+    }
+
+    // Normalized as `e` followed by an optional `-` and the exponent's digits.
+    fn exponent_parser<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        one_of("eE")
+            .ignore_then(one_of("+-").or_not())
+            .then(dec_parser())
+            .map(|(sign, exponent)| {
+                let mut res = String::from("e");
+                if sign == Some('-') {
+                    res.push('-');
+                }
+                res.push_str(&exponent);
+                res
+            })
     }
 
-    fn whole_part_only_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    fn whole_part_only_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         dec_parser()
             .then_ignore(just(".").or_not())
-            .map(|mut v| {
-                v.push_str(&".0");
+            .then(exponent_parser().or_not())
+            .map(|(mut v, exponent)| {
+                v.push_str(".0");
+                v.push_str(&exponent.unwrap_or_default());
                 NumberLiteral::Float(v)
             })
     }
 
-    fn no_whole_part_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    fn no_whole_part_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         just(".")
             .ignore_then(dec_parser())
-            .map(|v| {
+            .then(exponent_parser().or_not())
+            .map(|(v, exponent)| {
                 let mut res = String::from("0.");
                 res.push_str(&v);
+                res.push_str(&exponent.unwrap_or_default());
                 NumberLiteral::Float(res)
             })
     }
 
-    fn whole_and_fractional_part_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
+    fn whole_and_fractional_part_float_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
         dec_parser()
             .then(just("."))
             .then(dec_parser())
-            .map(|((whole, _), fractional)| {
-                let res = whole + "." + &fractional;
+            .then(exponent_parser().or_not())
+            .map(|(((whole, _), fractional), exponent)| {
+                let res = whole + "." + &fractional + &exponent.unwrap_or_default();
                 NumberLiteral::Float(res)
             })
     }
 
-    fn number_literal_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral> {
-        choice((
-            bin_integer_parser(),
-            oct_integer_parser(),
-            hex_integer_parser(),
-            whole_and_fractional_part_float_parser(),
-            no_whole_part_float_parser(),
-            whole_part_only_float_parser()
-        ))
+    fn number_literal_parser<'src>() -> impl Parser<'src, &'src str, NumberLiteral, Extra<'src>> {
+        just("-").or_not()
+            .then(choice((
+                bin_integer_parser(),
+                oct_integer_parser(),
+                hex_integer_parser(),
+                whole_and_fractional_part_float_parser(),
+                no_whole_part_float_parser(),
+                whole_part_only_float_parser()
+            )))
+            .map(|(sign, literal)| {
+                match sign {
+                    Some(_) => literal.negated(),
+                    None => literal,
+                }
+            })
     }
 
-    fn string_literal_parser<'src>() -> impl Parser<'src, &'src str, StringLiteral> {
-        at_least_x_alphanumeric_parser(0)
-            .map(|v| v.iter().collect::<String>())
-            .padded_by(just('"'))
-            .map(StringLiteral)
+    // Characters with a meaning after a backslash, `u` starting a Unicode escape.
+    const ESCAPED_CHARS: &str = "\"\\ntru";
+
+    // `\u{...}` takes between 1 and 6 hex digits, and must name a valid Unicode scalar value.
+    fn unicode_escape_parser<'src>() -> impl Parser<'src, &'src str, char, Extra<'src>> {
+        just('u')
+            .ignore_then(
+                one_of(HEX_DIGITS)
+                    .repeated().at_least(1).at_most(6)
+                    .collect::<String>()
+                    .delimited_by(just('{'), just('}'))
+            )
+            .try_map(|digits, span| {
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Rich::custom(span, "invalid Unicode scalar value"))
+            })
     }
 
-    // TODO: Support a."b.c" parsing as ["a", "b.c"]
-    // TODO: Support escaped double quotes, escaped dots, and escaped escapes
-    // TODO: Test it
-    fn reference_parser<'src>() -> impl Parser<'src, &'src str, Reference> {
-        just("&")
+    // Unknown escapes are kept as written, backslash included, as regex patterns such as `"^v(\d+)"` need.
+    fn escape_parser<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        just('\\')
             .ignore_then(
-                at_least_x_alphanumeric_parser(1)
-                .map(|v| v.iter().collect::<String>())
-                .then(
-                    just(".")
-                    .ignore_then(at_least_x_alphanumeric_parser(1))
-                    .map(|v| v.iter().collect::<String>())
-                    .repeated()
-                    .collect::<Vec<String>>()
-                )
-                .map(|(first, mut rest)| {
-                    rest.insert(0, first);
-                    rest
-                })
+                choice((
+                    just('"'),
+                    just('\\'),
+                    just('n').to('\n'),
+                    just('t').to('\t'),
+                    just('r').to('\r'),
+                    unicode_escape_parser(),
+                ))
+                .map(String::from)
+                .or(none_of(ESCAPED_CHARS).map(|c| format!("\\{}", c))),
             )
-            .map(Reference)
     }
 
-    fn argument_parser<'src>() -> impl Parser<'src, &'src str, Argument> {
+    fn escaped_string_literal_parser<'src>() -> impl Parser<'src, &'src str, StringLiteral, Extra<'src>> {
+        none_of("\\\"")
+            .map(String::from)
+            .or(escape_parser())
+            .repeated()
+            .collect::<Vec<String>>()
+            .delimited_by(just('"'), just('"'))
+            .map(|parts| StringLiteral(parts.concat()))
+    }
+
+    // Single-quoted strings are taken as is, without any escaping.
+    fn raw_string_literal_parser<'src>() -> impl Parser<'src, &'src str, StringLiteral, Extra<'src>> {
+        none_of("'")
+            .repeated()
+            .collect::<String>()
+            .delimited_by(just('\''), just('\''))
+            .map(StringLiteral)
+    }
+
+    fn string_literal_parser<'src>() -> impl Parser<'src, &'src str, StringLiteral, Extra<'src>> {
         choice((
+            escaped_string_literal_parser(),
+            raw_string_literal_parser(),
+        ))
+    }
+
+    // Escapes let a bare key contain characters that are otherwise part of the reference syntax.
+    fn reference_key_escape_parser<'src>() -> impl Parser<'src, &'src str, char, Extra<'src>> {
+        just('\\')
+            .ignore_then(one_of(".\"\\[]"))
+    }
+
+    fn bare_reference_key_parser<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        any()
+            .filter(|c| is_bare_reference_char(*c))
+            .or(reference_key_escape_parser())
+            .repeated().at_least(1)
+            .collect::<String>()
+    }
+
+    // `[-1]` is the last element of a sequence, `[-2]` the one before, and so on.
+    fn reference_index_parser<'src>() -> impl Parser<'src, &'src str, ReferenceSegment, Extra<'src>> {
+        just('-').or_not()
+            .then(dec_parser())
+            .try_map(|(sign, digits), span| {
+                let index = match sign {
+                    Some(_) => format!("-{}", digits),
+                    None => digits,
+                };
+                index.parse().map_err(|_| Rich::custom(span, "index is too large"))
+            })
+            .delimited_by(just('['), just(']'))
+            .map(ReferenceSegment::Index)
+    }
+
+    fn reference_match_operand_parser<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+        choice((
+            string_literal_parser().map(|literal| literal.0),
+            bare_reference_key_parser(),
+        ))
+    }
+
+    // `[name=web]`, with either side possibly quoted.
+    fn reference_match_parser<'src>() -> impl Parser<'src, &'src str, ReferenceSegment, Extra<'src>> {
+        reference_match_operand_parser()
+            .then_ignore(just('='))
+            .then(reference_match_operand_parser())
+            .delimited_by(just('['), just(']'))
+            .map(|(field, value)| ReferenceSegment::Match { field, value })
+    }
+
+    fn reference_segment_parser<'src>() -> impl Parser<'src, &'src str, ReferenceSegment, Extra<'src>> {
+        choice((
+            reference_index_parser(),
+            reference_match_parser(),
+            string_literal_parser().map(|literal| ReferenceSegment::Key(literal.0)),
+            bare_reference_key_parser().map(ReferenceSegment::Key),
+        ))
+    }
+
+    // Segments are separated by dots, except for brackets which can directly follow the previous segment.
+    fn reference_tail_parser<'src>() -> impl Parser<'src, &'src str, Vec<ReferenceSegment>, Extra<'src>> {
+        choice((
+            just(".").ignore_then(reference_segment_parser()),
+            reference_index_parser(),
+            reference_match_parser(),
+        ))
+        .repeated()
+        .collect::<Vec<ReferenceSegment>>()
+    }
+
+    fn reference_segments_parser<'src>() -> impl Parser<'src, &'src str, Vec<ReferenceSegment>, Extra<'src>> {
+        reference_segment_parser()
+            .then(reference_tail_parser())
+            .map(|(first, mut rest)| {
+                rest.insert(0, first);
+                rest
+            })
+    }
+
+    // `&.a` is a sibling of the operator, and each additional dot goes up one more level.
+    fn dotted_relative_reference_parser<'src>() -> impl Parser<'src, &'src str, Reference, Extra<'src>> {
+        just(".")
+            .repeated().at_least(1)
+            .count()
+            .then(reference_segments_parser().or_not())
+            .map(|(levels, segments)| Reference {
+                anchor: ReferenceAnchor::Relative(levels),
+                segments: segments.unwrap_or_default(),
+            })
+    }
+
+    // `&^2.a` is the same as `&..a`.
+    fn caret_relative_reference_parser<'src>() -> impl Parser<'src, &'src str, Reference, Extra<'src>> {
+        just("^")
+            .ignore_then(dec_parser())
+            .try_map(|levels, span| {
+                match levels.parse::<usize>() {
+                    Ok(levels) if levels > 0 => Ok(levels),
+                    _ => Err(Rich::custom(span, "expected at least one level up")),
+                }
+            })
+            .then(reference_tail_parser())
+            .map(|(levels, segments)| Reference {
+                anchor: ReferenceAnchor::Relative(levels),
+                segments,
+            })
+    }
+
+    // `&$item.name` starts from the value bound to `item`.
+    fn variable_reference_parser<'src>() -> impl Parser<'src, &'src str, Reference, Extra<'src>> {
+        just(VARIABLE_SIGIL)
+            .ignore_then(text::ascii::ident())
+            .then(reference_tail_parser())
+            .map(|(name, segments): (&str, _)| Reference {
+                anchor: ReferenceAnchor::Variable(name.to_owned()),
+                segments,
+            })
+    }
+
+    fn reference_parser<'src>() -> impl Parser<'src, &'src str, Reference, Extra<'src>> {
+        just("&")
+            .ignore_then(choice((
+                dotted_relative_reference_parser(),
+                caret_relative_reference_parser(),
+                variable_reference_parser(),
+                reference_segments_parser().map(|segments| Reference {
+                    anchor: ReferenceAnchor::Root,
+                    segments,
+                }),
+            )))
+    }
+
+    fn boolean_literal_parser<'src>() -> impl Parser<'src, &'src str, BooleanLiteral, Extra<'src>> {
+        text::ascii::ident()
+            .try_map(|word: &str, span| match word {
+                "true" => Ok(BooleanLiteral(true)),
+                "false" => Ok(BooleanLiteral(false)),
+                _ => Err(Rich::custom(span, "expected true or false")),
+            })
+    }
+
+    fn keyword_parser<'src>() -> impl Parser<'src, &'src str, Keyword, Extra<'src>> {
+        text::ascii::ident()
+            .try_map(|word: &str, span| {
+                Keyword::ALL.iter()
+                    .find(|keyword| keyword.as_str() == word)
+                    .copied()
+                    .ok_or_else(|| Rich::custom(span, "unknown keyword"))
+            })
+    }
+
+    // `expr` parses a call without its parentheses, as arguments can themselves be calls.
+    fn argument_parser<'src>(expr: impl Parser<'src, &'src str, Expr, Extra<'src>>) -> impl Parser<'src, &'src str, Argument, Extra<'src>> {
+        let alternative = choice((
             number_literal_parser().map(Argument::NumberLiteral),
             string_literal_parser().map(Argument::StringLiteral),
-            reference_parser().map(Argument::Reference)
-        ))
+            reference_parser().map(Argument::Reference),
+            boolean_literal_parser().map(Argument::BooleanLiteral),
+            keyword_parser().map(Argument::Keyword),
+            expr.delimited_by(just('('), just(')')).map(|expr| Argument::Expr(Box::new(expr))),
+        ));
+
+        alternative
+            .separated_by(just("||").padded_by(whitespace()))
+            .at_least(1)
+            .collect::<Vec<Argument>>()
+            .map(|mut alternatives| match alternatives.len() {
+                1 => alternatives.remove(0),
+                _ => Argument::Fallback(alternatives),
+            })
     }
 
-    fn expr_parser<'src>() -> impl Parser<'src, &'src str, Expr> {
+    // A call, as in `grab &a`, without any parentheses.
+    fn call_parser<'src>() -> impl Parser<'src, &'src str, Expr, Extra<'src>> {
+        recursive(|call| {
             let arguments = 
                 whitespace().at_least(1)
-                .ignore_then(argument_parser()) 
+                .ignore_then(argument_parser(call.clone()).map_with(|argument, extra| (argument, extra.span().into_range())).boxed())
                 .repeated()
-                .collect::<Vec<Argument>>()
+                .collect::<Vec<(Argument, Span)>>()
                 .then_ignore(whitespace());
 
-            let inner_parser =
-                whitespace()
-                .ignore_then(operator_parser())
+            // `defer grab &a` is `defer (grab &a)`, the deferred call's span leaving out trailing whitespace.
+            let deferred = whitespace()
+                .ignore_then(just::<_, &str, Extra<'src>>(DEFER_KEYWORD).map_with(|_, extra| {
+                    let span: Span = extra.span().into_range();
+                    (OperatorName(DEFER_KEYWORD.to_owned()), span)
+                }))
+                .then_ignore(whitespace().at_least(1))
+                .then(call.map_with(|inner, extra| {
+                    let span: Span = extra.span().into_range();
+                    let slice: &str = extra.slice();
+                    (inner, span.start..span.start + slice.trim_end().len())
+                }))
+                .map(|((name, name_span), (inner, inner_span))| Expr {
+                    name,
+                    arguments: vec![Argument::Expr(Box::new(inner))],
+                    location: ExprLocation { text: Rc::from(""), name: name_span, arguments: vec![inner_span], origin: None },
+                });
+
+            let call = whitespace()
+                .ignore_then(operator_parser().map_with(|name, extra| (name, extra.span().into_range())).boxed())
                 .then(arguments)
                 .then_ignore(whitespace())
-                .map(|expr| Expr { name: expr.0, arguments: expr.1 });
+                .map(|((name, name_span), arguments)| {
+                    let (arguments, spans) = arguments.into_iter().unzip();
+                    Expr {
+                        name,
+                        arguments,
+                        location: ExprLocation { text: Rc::from(""), name: name_span, arguments: spans, origin: None },
+                    }
+                });
 
+            choice((deferred.boxed(), call.boxed()))
+        })
+    }
 
+    fn expr_parser<'src>() -> impl Parser<'src, &'src str, Expr, Extra<'src>> {
             let opening_double_parens = just("((");
             let closing_double_parens: chumsky::primitive::Just<&'static str, _, _> = just("))");
 
-            let double_parens = inner_parser
+            call_parser()
                 .delimited_by(opening_double_parens, closing_double_parens)
-                .padded();
-
-            double_parens
+                .padded()
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::data::{DataKey, DataKeyPath};
 
         #[test]
         fn test_operator_parser() {
@@ -229,6 +909,13 @@ pub mod parser {
             assert_eq!(parser.parse("a123").unwrap(), OperatorName("a123".to_owned()));
             assert_ne!(parser.parse("a321").unwrap(), OperatorName("a123".to_owned()));
 
+            assert_eq!(parser.parse("base64-decode").unwrap(), OperatorName("base64-decode".to_owned()));
+            // Names are a single word, `by` being a keyword.
+            let expr = Expr::parse("(( sort by \"name\" &list ))").unwrap();
+            assert_eq!(expr.name, OperatorName("sort".to_owned()));
+            assert_eq!(expr.arguments[0], Argument::Keyword(Keyword::By));
+            assert!(parser.parse("-a").has_errors());
+
             assert!(!parser.parse("").has_output());
 
             assert!(parser.parse("123").has_errors());
@@ -318,6 +1005,356 @@ pub mod parser {
             assert!(parser.parse(" 0x101 0x010 ").has_errors());
         }
 
+        #[test]
+        fn test_number_literal_parser() {
+            let parser = number_literal_parser();
+
+            assert_eq!(parser.parse("12").unwrap(), NumberLiteral::Float("12.0".to_owned()));
+            assert_eq!(parser.parse("12.").unwrap(), NumberLiteral::Float("12.0".to_owned()));
+            assert_eq!(parser.parse(".5").unwrap(), NumberLiteral::Float("0.5".to_owned()));
+            assert_eq!(parser.parse("1.25").unwrap(), NumberLiteral::Float("1.25".to_owned()));
+            assert_eq!(parser.parse("0x1F").unwrap(), NumberLiteral::Hex("1f".to_owned()));
+
+            assert_eq!(parser.parse("-12").unwrap(), NumberLiteral::Float("-12.0".to_owned()));
+            assert_eq!(parser.parse("-.5").unwrap(), NumberLiteral::Float("-0.5".to_owned()));
+            assert_eq!(parser.parse("-0xff").unwrap(), NumberLiteral::Hex("-ff".to_owned()));
+            assert_eq!(parser.parse("-0o17").unwrap(), NumberLiteral::Oct("-17".to_owned()));
+            assert_eq!(parser.parse("-0b11").unwrap(), NumberLiteral::Bin("-11".to_owned()));
+
+            assert_eq!(parser.parse("1e9").unwrap(), NumberLiteral::Float("1.0e9".to_owned()));
+            assert_eq!(parser.parse("1E+9").unwrap(), NumberLiteral::Float("1.0e9".to_owned()));
+            assert_eq!(parser.parse("2.5e-3").unwrap(), NumberLiteral::Float("2.5e-3".to_owned()));
+            assert_eq!(parser.parse(".5e2").unwrap(), NumberLiteral::Float("0.5e2".to_owned()));
+            assert_eq!(parser.parse("-1e9").unwrap(), NumberLiteral::Float("-1.0e9".to_owned()));
+
+            assert_eq!(parser.parse("1_000_000").unwrap(), NumberLiteral::Float("1000000.0".to_owned()));
+            assert_eq!(parser.parse("1_000.000_1").unwrap(), NumberLiteral::Float("1000.0001".to_owned()));
+            assert_eq!(parser.parse("0xdead_BEEF").unwrap(), NumberLiteral::Hex("deadbeef".to_owned()));
+            assert_eq!(parser.parse("0b1010_1010").unwrap(), NumberLiteral::Bin("10101010".to_owned()));
+            assert_eq!(parser.parse("1e1_0").unwrap(), NumberLiteral::Float("1.0e10".to_owned()));
+
+            assert!(parser.parse("_1").has_errors());
+            assert!(parser.parse("0x_1").has_errors());
+            assert!(parser.parse("1._5").has_errors());
+            assert!(parser.parse("1_").has_errors());
+            assert!(parser.parse("0xf_").has_errors());
+            assert!(parser.parse("1__0").has_errors());
+            assert!(parser.parse("1_.5").has_errors());
+            assert!(parser.parse("1e").has_errors());
+            assert!(parser.parse("1e_5").has_errors());
+            assert!(parser.parse("--1").has_errors());
+            assert!(parser.parse("- 1").has_errors());
+            assert!(parser.parse("e9").has_errors());
+
+            assert!(parser.parse("").has_errors());
+            assert!(parser.parse(" ").has_errors());
+            assert!(parser.parse(" 1").has_errors());
+            assert!(parser.parse("1 ").has_errors());
+        }
+
+        fn evaluate(input: &str) -> Result<f64, NumberLiteralError> {
+            let literal = number_literal_parser().parse(input).unwrap();
+            (&literal).try_into()
+        }
+
+        #[test]
+        fn test_number_literal_evaluation() {
+            assert_eq!(evaluate("12"), Ok(12.0));
+            assert_eq!(evaluate("-1.5"), Ok(-1.5));
+            assert_eq!(evaluate(".25"), Ok(0.25));
+            assert_eq!(evaluate("1e9"), Ok(1e9));
+            assert_eq!(evaluate("-2.5e-3"), Ok(-2.5e-3));
+            assert_eq!(evaluate("1_000_000"), Ok(1_000_000.0));
+
+            assert_eq!(evaluate("0xff"), Ok(255.0));
+            assert_eq!(evaluate("-0xff"), Ok(-255.0));
+            assert_eq!(evaluate("0o17"), Ok(15.0));
+            assert_eq!(evaluate("0b1010_1010"), Ok(170.0));
+
+            assert_eq!(evaluate("0x20000000000000"), Ok(9007199254740992.0));
+            assert_eq!(evaluate("0x20000000000001"), Err(NumberLiteralError::Overflow));
+            assert_eq!(evaluate("0xffffffffffffffffff"), Err(NumberLiteralError::Overflow));
+            assert_eq!(evaluate(&format!("0b1{}", "0".repeat(64))), Err(NumberLiteralError::Overflow));
+            assert_eq!(evaluate("1e400"), Err(NumberLiteralError::Overflow));
+            assert_eq!(evaluate("-1e400"), Err(NumberLiteralError::Overflow));
+
+            assert_eq!(f64::try_from(&NumberLiteral::Hex("xyz".to_owned())), Err(NumberLiteralError::Invalid));
+            assert_eq!(f64::try_from(&NumberLiteral::Float("NaN".to_owned())), Err(NumberLiteralError::Invalid));
+            assert_eq!(NumberLiteralError::Overflow.to_string(), "it does not fit in a number without losing precision");
+        }
+
+        #[test]
+        fn test_string_literal_parser() {
+            let parser = string_literal_parser();
+
+            assert_eq!(parser.parse("\"\"").unwrap(), StringLiteral("".to_owned()));
+            assert_eq!(parser.parse("\"toto\"").unwrap(), StringLiteral("toto".to_owned()));
+            assert_eq!(parser.parse("\"Please set the DB host\"").unwrap(), StringLiteral("Please set the DB host".to_owned()));
+            assert_eq!(parser.parse("\"a.b-c/d, (e)!\"").unwrap(), StringLiteral("a.b-c/d, (e)!".to_owned()));
+            assert_eq!(parser.parse("\"héhé ✓ 日本\"").unwrap(), StringLiteral("héhé ✓ 日本".to_owned()));
+            assert_eq!(parser.parse("\"it's\"").unwrap(), StringLiteral("it's".to_owned()));
+
+            assert_eq!(parser.parse(r#""say \"hi\"""#).unwrap(), StringLiteral("say \"hi\"".to_owned()));
+            assert_eq!(parser.parse(r#""a\\b""#).unwrap(), StringLiteral("a\\b".to_owned()));
+            assert_eq!(parser.parse(r#""a\nb\tc\rd""#).unwrap(), StringLiteral("a\nb\tc\rd".to_owned()));
+            assert_eq!(parser.parse(r#""\u{e9}\u{1F600}""#).unwrap(), StringLiteral("é😀".to_owned()));
+
+            assert_eq!(parser.parse("''").unwrap(), StringLiteral("".to_owned()));
+            assert_eq!(parser.parse(r#"'C:\path\n "quoted"'"#).unwrap(), StringLiteral(r#"C:\path\n "quoted""#.to_owned()));
+
+            assert_eq!(parser.parse(r#""^v(\d+)\.\q""#).unwrap(), StringLiteral(r"^v(\d+)\.\q".to_owned()));
+            assert!(parser.parse(r#""\u{}""#).has_errors());
+            assert!(parser.parse(r#""\u{1234567}""#).has_errors());
+            assert!(parser.parse(r#""\u{d800}""#).has_errors());
+            assert!(parser.parse(r#""\u00e9""#).has_errors());
+            assert!(parser.parse(r#""unterminated"#).has_errors());
+            assert!(parser.parse(r#""trailing\""#).has_errors());
+            assert!(parser.parse("'it's'").has_errors());
+            assert!(parser.parse("toto").has_errors());
+
+            assert!(parser.parse("").has_errors());
+            assert!(parser.parse(" \"toto\"").has_errors());
+            assert!(parser.parse("\"toto\" ").has_errors());
+        }
+
+        #[test]
+        fn test_expr_parser() {
+            let parser = expr_parser();
+
+            assert_eq!(
+                parser.parse("(( expect \"Please set the DB host\" ))").unwrap(),
+                Expr {
+                    name: OperatorName("expect".to_owned()),
+                    arguments: vec![Argument::StringLiteral(StringLiteral("Please set the DB host".to_owned()))],
+                    location: ExprLocation::default(),
+                }
+            );
+            assert_eq!(
+                parser.parse("(( concat 'a))' \"b\\\"\" -1e3 ))").unwrap(),
+                Expr {
+                    name: OperatorName("concat".to_owned()),
+                    arguments: vec![
+                        Argument::StringLiteral(StringLiteral("a))".to_owned())),
+                        Argument::StringLiteral(StringLiteral("b\"".to_owned())),
+                        Argument::NumberLiteral(NumberLiteral::Float("-1.0e3".to_owned())),
+                    ],
+                    location: ExprLocation::default(),
+                }
+            );
+
+            let nested = Expr::parse("(( concat (grab &a) \"-\" ( calc \"&b + 1\" (x)))) ").unwrap();
+            assert_eq!(nested.name, OperatorName("concat".to_owned()));
+            assert_eq!(nested.location.arguments, vec![10..19, 20..23, 24..44]);
+            match (&nested.arguments[0], &nested.arguments[2]) {
+                (Argument::Expr(grab), Argument::Expr(calc)) => {
+                    assert_eq!(grab.arguments, vec![Argument::Reference(absolute(vec![key("a")]))]);
+                    assert_eq!(calc.location.name, 26..30);
+                    assert_eq!(calc.location.slice(&calc.location.arguments[0]), "\"&b + 1\"");
+                    assert!(matches!(&calc.arguments[1], Argument::Expr(x) if x.arguments.is_empty() && x.location.text == nested.location.text));
+                },
+                other => panic!("expected nested calls, found {:?}", other),
+            }
+
+            let fallback = Expr::parse("(( grab &a.b || (grab &c)|| \"default\" ))").unwrap();
+            assert_eq!(fallback.location.arguments, vec![8..37]);
+            match &fallback.arguments[..] {
+                [Argument::Fallback(alternatives)] => {
+                    assert_eq!(alternatives.len(), 3);
+                    assert!(matches!(&alternatives[1], Argument::Expr(grab) if grab.name.0 == "grab" && grab.location.text == fallback.location.text));
+                    assert_eq!(alternatives[2], Argument::StringLiteral(StringLiteral("default".to_owned())));
+                },
+                other => panic!("expected a fallback, found {:?}", other),
+            }
+
+            let deferred = Expr::parse("((defer  grab &a  ))").unwrap();
+            assert_eq!(deferred.location.arguments, vec![9..16]);
+            assert_eq!(deferred, Expr::parse("(( defer (grab &a) ))").unwrap());
+            assert!(matches!(&Expr::parse("(( deferred &a ))").unwrap().arguments[..], [Argument::Reference(_)]));
+
+            assert_eq!(Expr::parse("(( empty hash ))").unwrap().arguments, vec![Argument::Keyword(Keyword::Hash)]);
+            assert_eq!(
+                Expr::parse("(( ternary &a true false ))").unwrap().arguments[1..],
+                [Argument::BooleanLiteral(BooleanLiteral(true)), Argument::BooleanLiteral(BooleanLiteral(false))]
+            );
+            assert!(Expr::parse("(( empty hashes ))").is_err());
+            assert!(Expr::parse("(( empty hash.a ))").is_err());
+            assert!(is_replace_marker("((replace-subtree ))") && !is_replace_marker("(( replace-subtree &a ))") && !is_replace_marker("replace-subtree"));
+            // Not to be confused with the `replace` operator.
+            assert!(!is_replace_marker("(( replace ))"));
+
+            assert!(parser.parse("(( grab &a || ))").has_errors());
+            assert!(parser.parse("(( grab &a | &b ))").has_errors());
+            assert!(parser.parse("(( concat (grab &a ))").has_errors());
+            assert!(parser.parse("(( concat () ))").has_errors());
+            assert!(parser.parse("(( expect \"unterminated ))").has_errors());
+            assert!(parser.parse("(( expect \"a\"\"b\" ))").has_errors());
+        }
+
+        fn key(key: &str) -> ReferenceSegment {
+            ReferenceSegment::Key(key.to_owned())
+        }
+
+        fn absolute(segments: Vec<ReferenceSegment>) -> Reference {
+            Reference { anchor: ReferenceAnchor::Root, segments }
+        }
+
+        fn relative(levels: usize, segments: Vec<ReferenceSegment>) -> Reference {
+            Reference { anchor: ReferenceAnchor::Relative(levels), segments }
+        }
+
+        fn variable(name: &str, segments: Vec<ReferenceSegment>) -> Reference {
+            Reference { anchor: ReferenceAnchor::Variable(name.to_owned()), segments }
+        }
+
+        #[test]
+        fn test_canonical_display() {
+            for canonical in [
+                "(( param ))",
+                "(( concat \"a\\\"b\\\\\\n\\u{1}\" 1.5 -0x1f 0o7 0b10 -1.0e-3 ))",
+                "(( grab &a.\"b.c\"[0][name=\"x y\"].d ))",
+                "(( grab &..a &^1.b &[-1] &. ))",
+                "(( concat (grab &a || (grab &b) || \"c\") \"-\" ))",
+                "(( defer defer grab &a ))",
+                "(( empty array ))",
+                "(( map &$item.ports[0] &$key ))",
+            ] {
+                let expr = Expr::parse(canonical).unwrap();
+                let written = expr.to_string();
+                assert_eq!(Expr::parse(&written).unwrap(), expr, "{} was written as {}", canonical, written);
+            }
+
+            assert_eq!(Expr::parse("((concat   'a\"'  0XFF &^2.b ))").unwrap().to_string(), "(( concat \"a\\\"\" 0xff &..b ))");
+            assert_eq!(Expr::parse("(( defer (grab &a) ))").unwrap().to_string(), "(( defer grab &a ))");
+        }
+
+        #[test]
+        fn test_reference_parser() {
+            let parser = reference_parser();
+
+            assert_eq!(parser.parse("&a").unwrap(), absolute(vec![key("a")]));
+            assert_eq!(parser.parse("&a.b.c").unwrap(), absolute(vec![key("a"), key("b"), key("c")]));
+            assert_eq!(parser.parse("&my-service.app_name").unwrap(), absolute(vec![key("my-service"), key("app_name")]));
+            assert_eq!(parser.parse("&jobs.0.name").unwrap(), absolute(vec![key("jobs"), key("0"), key("name")]));
+            assert_eq!(parser.parse("&données.clé").unwrap(), absolute(vec![key("données"), key("clé")]));
+            assert_eq!(parser.parse("&$item").unwrap(), variable("item", vec![]));
+            assert_eq!(parser.parse("&$item.ports[-1]").unwrap(), variable("item", vec![key("ports"), ReferenceSegment::Index(-1)]));
+            assert!(parser.parse("&$").has_errors());
+            assert!(parser.parse("&$item.").has_errors());
+
+            assert_eq!(
+                parser.parse("&labels.\"kubernetes.io/name\"").unwrap(),
+                absolute(vec![key("labels"), key("kubernetes.io/name")])
+            );
+            assert_eq!(parser.parse("&a.\"b.c\"").unwrap(), absolute(vec![key("a"), key("b.c")]));
+            assert_eq!(parser.parse(r#"&a."say \"hi\"""#).unwrap(), absolute(vec![key("a"), key("say \"hi\"")]));
+            assert_eq!(parser.parse("&a.'b\\c'").unwrap(), absolute(vec![key("a"), key("b\\c")]));
+            assert_eq!(parser.parse("&\"\"").unwrap(), absolute(vec![key("")]));
+            assert_eq!(parser.parse(r#"&a\.b.c\\d"#).unwrap(), absolute(vec![key("a.b"), key("c\\d")]));
+
+            assert_eq!(parser.parse("&a[0]").unwrap(), absolute(vec![key("a"), ReferenceSegment::Index(0)]));
+            assert_eq!(parser.parse("&a.[-1]").unwrap(), absolute(vec![key("a"), ReferenceSegment::Index(-1)]));
+            assert_eq!(
+                parser.parse("&a[1][-2].b").unwrap(),
+                absolute(vec![key("a"), ReferenceSegment::Index(1), ReferenceSegment::Index(-2), key("b")])
+            );
+            assert_eq!(parser.parse("&[2]").unwrap(), absolute(vec![ReferenceSegment::Index(2)]));
+
+            assert!(parser.parse("&").has_errors());
+            assert!(parser.parse("a.b").has_errors());
+            assert!(parser.parse("&a.").has_errors());
+            assert!(parser.parse("&a..b").has_errors());
+            assert!(parser.parse("&a b").has_errors());
+            assert!(parser.parse("&a[]").has_errors());
+            assert!(parser.parse("&a[b]").has_errors());
+            assert!(parser.parse("&a[1").has_errors());
+            assert!(parser.parse("&a.\"b").has_errors());
+            assert!(parser.parse("&a\\b").has_errors());
+
+            assert!(parser.parse("&a.b.").has_errors());
+            assert!(parser.parse("&a.^1").has_errors());
+
+            assert!(parser.parse("").has_errors());
+            assert!(parser.parse(" &a").has_errors());
+            assert!(parser.parse("&a ").has_errors());
+        }
+
+        #[test]
+        fn test_relative_reference_parser() {
+            let parser = reference_parser();
+
+            assert_eq!(parser.parse("&.sibling").unwrap(), relative(1, vec![key("sibling")]));
+            assert_eq!(parser.parse("&..parent.key").unwrap(), relative(2, vec![key("parent"), key("key")]));
+            assert_eq!(parser.parse("&...a").unwrap(), relative(3, vec![key("a")]));
+            assert_eq!(parser.parse("&.[0]").unwrap(), relative(1, vec![ReferenceSegment::Index(0)]));
+            assert_eq!(parser.parse("&.\"b.c\"").unwrap(), relative(1, vec![key("b.c")]));
+            assert_eq!(parser.parse("&..").unwrap(), relative(2, vec![]));
+
+            assert_eq!(parser.parse("&^2.key").unwrap(), relative(2, vec![key("key")]));
+            assert_eq!(parser.parse("&^1.a.b").unwrap(), relative(1, vec![key("a"), key("b")]));
+            assert_eq!(parser.parse("&^10[-1]").unwrap(), relative(10, vec![ReferenceSegment::Index(-1)]));
+            assert_eq!(parser.parse("&^3").unwrap(), relative(3, vec![]));
+
+            assert!(parser.parse("&^0.a").has_errors());
+            assert!(parser.parse("&^.a").has_errors());
+            assert!(parser.parse("&^-1.a").has_errors());
+            assert!(parser.parse("&^2a").has_errors());
+            assert!(parser.parse("&^2..a").has_errors());
+            assert!(parser.parse("&.a..b").has_errors());
+            assert!(parser.parse("&. a").has_errors());
+        }
+
+        fn field_match(field: &str, value: &str) -> ReferenceSegment {
+            ReferenceSegment::Match { field: field.to_owned(), value: value.to_owned() }
+        }
+
+        #[test]
+        fn test_match_reference_parser() {
+            let parser = reference_parser();
+
+            assert_eq!(
+                parser.parse("&jobs.[name=web].instances").unwrap(),
+                absolute(vec![key("jobs"), field_match("name", "web"), key("instances")])
+            );
+            assert_eq!(parser.parse("&jobs[id=3]").unwrap(), absolute(vec![key("jobs"), field_match("id", "3")]));
+            assert_eq!(
+                parser.parse("&jobs[\"app.kubernetes.io/name\"='my app']").unwrap(),
+                absolute(vec![key("jobs"), field_match("app.kubernetes.io/name", "my app")])
+            );
+            assert_eq!(parser.parse("&.[name=web]").unwrap(), relative(1, vec![field_match("name", "web")]));
+            assert_eq!(
+                parser.parse("&a[name=b][0]").unwrap(),
+                absolute(vec![key("a"), field_match("name", "b"), ReferenceSegment::Index(0)])
+            );
+
+            assert!(parser.parse("&jobs[name=]").has_errors());
+            assert!(parser.parse("&jobs[=web]").has_errors());
+            assert!(parser.parse("&jobs[name=web").has_errors());
+            assert!(parser.parse("&jobs[name = web]").has_errors());
+            assert!(parser.parse("&jobs[name=a=b]").has_errors());
+        }
+
+        #[test]
+        fn test_data_key_path_display_round_trip() {
+            let paths = vec![
+                vec!["a", "b", "c"],
+                vec!["my-service", "app_name"],
+                vec!["jobs", "0", "name"],
+                vec!["labels", "kubernetes.io/name"],
+                vec!["quotes \"and\" \\escapes\\", "new\nline"],
+                vec!["", "[0]", "(( x ))", "données"],
+            ];
+
+            for path in paths {
+                let data_key_path = DataKeyPath(path.iter().map(|key| DataKey::from(*key)).collect());
+                let displayed = format!("&{}", data_key_path);
+                let reference = reference_parser().parse(displayed.as_str()).unwrap();
+                assert_eq!(DataKeyPath::try_from(reference), Ok(data_key_path));
+            }
+
+            let path = DataKeyPath(vec!["a".into(), "b.c".into()]);
+            assert_eq!(path.to_string(), "a.\"b.c\"");
+        }
+
         // TODO: Finish unit testing
     }
 }
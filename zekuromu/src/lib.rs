@@ -1,3 +1,6 @@
 pub mod data;
 pub mod operators;
 pub mod raw;
+
+#[cfg(test)]
+mod test_helpers;
@@ -1,44 +1,203 @@
 //! Operators to enrich, prune, reference, or allow more complex array operations.
 //! Heavily inspired by [spruce](https://github.com/geofffranks/spruce).
 //! Operators declaration and definition happens here.
-//! Should ultimately support plugins in its API.
+//! Operators defined by other crates are made available through [`registry::OperatorRegistry`].
 
-use std::rc::Rc;
+use std::{collections::HashMap, fmt::Display, path::PathBuf, rc::Rc};
 
-use crate::{data::{DataKeyPath, OperatorData}, operators::native::NativeOperator};
+use crate::{data::{DataKey, DataKeyPath, OperatorData, ReferenceError, operators::{Argument, BooleanLiteral, Expr, Keyword, NumberLiteral, Reference, ReferenceAnchor, StringLiteral, VARIABLE_SIGIL}}, operators::{native::NativeOperator, registry::OperatorRegistry, signature::{ArgumentKind, ArgumentKinds}}};
 
 pub mod native;
+pub mod registry;
+pub mod signature;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[derive(Debug, Clone)]
 pub enum OperatorParsingErrorReason {
     NoneMatched,
+    /// No operator, native or registered, has that name.
+    UnknownOperator { suggestion: Option<String> },
     NameDoesNotMatch,
-    ArgumentsLengthDoesNotMatch,
-    ArgumentsTypesDoNotMatch,
+    /// `maximum` is `None` when the operator is variadic.
+    ArgumentsLengthDoesNotMatch { minimum: usize, maximum: Option<usize>, found: usize },
+    /// `position` starts at 0.
+    ArgumentTypeDoesNotMatch { position: usize, expected: Vec<ArgumentKind>, found: ArgumentKind },
+    /// The operator was disabled through [`registry::OperatorRegistry::disable`].
+    Disabled,
+    /// The argument at `position` has the right kind, but an invalid value, such as a malformed regex.
+    InvalidArgument { position: usize, reason: String },
+    /// The nested call at `position`, named `name`, could not be parsed.
+    InNestedOperator { position: usize, name: String, reason: Box<OperatorParsingErrorReason> },
     Unknown,
 }
 
-pub type OperatorParsingError = (Option<NativeOperator>, OperatorParsingErrorReason);
+fn arguments_count(count: usize) -> String {
+    match count {
+        1 => "1 argument".to_owned(),
+        _ => format!("{} arguments", count),
+    }
+}
+
+impl Display for OperatorParsingErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperatorParsingErrorReason::NoneMatched => write!(f, "unknown operator"),
+            OperatorParsingErrorReason::UnknownOperator { suggestion: None } => write!(f, "unknown operator"),
+            OperatorParsingErrorReason::UnknownOperator { suggestion: Some(suggestion) } => {
+                write!(f, "unknown operator, did you mean '{}'?", suggestion)
+            },
+            OperatorParsingErrorReason::NameDoesNotMatch => write!(f, "operator name does not match"),
+            OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum, maximum, found } => {
+                let expected = match maximum {
+                    Some(maximum) if maximum == minimum => arguments_count(*minimum),
+                    Some(maximum) => format!("{} to {}", minimum, arguments_count(*maximum)),
+                    None => format!("at least {}", arguments_count(*minimum)),
+                };
+                write!(f, "expected {}, found {}", expected, found)
+            },
+            OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position, expected, found } => {
+                write!(f, "argument {} expected {}, found {}", position + 1, ArgumentKinds(expected), found)
+            },
+            OperatorParsingErrorReason::Disabled => write!(f, "operator is disabled"),
+            OperatorParsingErrorReason::InvalidArgument { position, reason } => {
+                write!(f, "argument {} {}", position + 1, reason)
+            },
+            OperatorParsingErrorReason::InNestedOperator { position, name, reason } => {
+                write!(f, "argument {}, {}: {}", position + 1, name, reason)
+            },
+            OperatorParsingErrorReason::Unknown => write!(f, "could not parse operator"),
+        }
+    }
+}
+
+pub type OperatorParsingError = (Option<OperatorSource>, OperatorParsingErrorReason);
 
 #[derive(Debug, Clone)]
 pub enum OperatorExecutionErrorReason {
     Unimplemented,
-    ReferenceUnavailable,
+    ReferenceUnavailable(ReferenceError),
+    /// A plugin trapped, ran out of fuel, answered something invalid, or reported an error itself.
+    PluginFailed(String),
+    /// A file could not be read, or lies outside of the root directory files are read from.
+    FileUnavailable { path: PathBuf, reason: String },
+    /// Every alternative of a fallback chain was unavailable, each with its own error, in order.
+    NoAlternativeAvailable(Vec<OperatorExecutionErrorReason>),
     OtherError(String),
 }
 
-pub type OperatorExecutionErrorResult = Result<(), OperatorExecutionErrorReason>;
+impl Display for OperatorExecutionErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperatorExecutionErrorReason::Unimplemented => write!(f, "operator is not implemented"),
+            OperatorExecutionErrorReason::ReferenceUnavailable(error) => write!(f, "reference unavailable, {}", error),
+            OperatorExecutionErrorReason::PluginFailed(message) => write!(f, "plugin failed, {}", message),
+            OperatorExecutionErrorReason::FileUnavailable { path, reason } => write!(f, "cannot read '{}', {}", path.display(), reason),
+            OperatorExecutionErrorReason::NoAlternativeAvailable(reasons) => {
+                write!(f, "no alternative available")?;
+                for (index, reason) in reasons.iter().enumerate() {
+                    write!(f, ", {}) {}", index + 1, reason)?;
+                }
+                Ok(())
+            },
+            OperatorExecutionErrorReason::OtherError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// The value replacing the operator in the document.
+pub type OperatorExecutionResult = Result<OperatorData, OperatorExecutionErrorReason>;
+
+/// What executing an operator does to the document.
+#[derive(Debug, Clone)]
+pub enum OperatorEffect {
+    /// The value replaces the operator.
+    Replace(OperatorData),
+    /// The operator's key is removed from the mapping holding it, which the mapping is then merged into,
+    /// its own keys winning over the merged ones.
+    MergeIntoParent(HashMap<DataKey, OperatorData>),
+}
+
+pub type OperatorApplicationResult = Result<OperatorEffect, OperatorExecutionErrorReason>;
+
+/// Variables bound by enclosing operators, such as `map`, and referenced as `&$name`.
+/// Cheap to clone, inner bindings shadowing outer ones.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings(Rc<HashMap<String, OperatorData>>);
+
+impl Bindings {
+    pub fn get(&self, name: &str) -> Option<&OperatorData> {
+        self.0.get(name)
+    }
+
+    /// These bindings, along with `name` bound to `value`.
+    pub fn with(&self, name: impl Into<String>, value: OperatorData) -> Bindings {
+        let mut inner = self.0.as_ref().clone();
+        inner.insert(name.into(), value);
+        Bindings(Rc::new(inner))
+    }
+}
+
+/// What an operator is evaluated against: the whole document, where the operator lies in it,
+/// and the variables bound there.
+#[derive(Debug, Clone)]
+pub struct EvaluationContext<'a> {
+    pub root: &'a OperatorData,
+    pub path: DataKeyPath,
+    pub bindings: Bindings,
+}
+
+impl<'a> EvaluationContext<'a> {
+    /// Without any variable bound, as for operators lying in the document itself.
+    pub fn new(root: &'a OperatorData, path: DataKeyPath) -> Self {
+        EvaluationContext { root, path, bindings: Bindings::default() }
+    }
+
+    /// The same document and variables, at another path.
+    pub fn at(&self, path: DataKeyPath) -> Self {
+        EvaluationContext { path, ..self.clone() }
+    }
+
+    pub fn bind(&self, name: impl Into<String>, value: OperatorData) -> Self {
+        EvaluationContext { bindings: self.bindings.with(name, value), ..self.clone() }
+    }
+
+    /// Same as [`OperatorData::resolve`], from where the operator lies,
+    /// variables being followed from the value bound to them, as if it lay at `$name`.
+    pub fn resolve(&self, reference: &Reference) -> Result<(DataKeyPath, &OperatorData), ReferenceError> {
+        match &reference.anchor {
+            ReferenceAnchor::Variable(name) => {
+                let value = self.bindings.get(name)
+                    .ok_or_else(|| ReferenceError::UnboundVariable(name.clone()))?;
+                let path = DataKeyPath::from(DataKey::from(format!("{}{}", VARIABLE_SIGIL, name)));
+                value.follow(path, &reference.segments)
+            },
+            _ => self.root.resolve(reference, &self.path),
+        }
+    }
+}
 
 pub trait OperatorPayload : std::fmt::Debug {
-    fn execute(&self, data: &mut OperatorData, path: &DataKeyPath) -> OperatorExecutionErrorResult;
+    /// This is also the value of the operator when it is nested in another.
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult;
+
+    /// Replaces the operator with the value it executes to, unless overridden.
+    fn apply(&self, context: &EvaluationContext) -> OperatorApplicationResult {
+        self.execute(context).map(OperatorEffect::Replace)
+    }
 }
 
 #[derive(Debug, Clone)]
-enum OperatorSource {
+pub enum OperatorSource {
     Native(NativeOperator),
+    /// Registered by name through an [`registry::OperatorRegistry`].
+    Registered(String),
+    /// Exported by a WebAssembly module, see the `wasm` module.
+    Wasm { plugin: String, name: String },
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Ranks are executed in the order they are declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OperatorPriorityRank {
     /// Mostly for `param`.
     First,
@@ -61,10 +220,100 @@ pub struct Operator {
     source: OperatorSource,
     payload: Rc<dyn OperatorPayload>,
     priority: OperatorPriorityRank,
+    /// As written, nested calls included.
+    expr: Rc<Expr>,
 }
 
 impl Operator {
-    pub fn execute(&self, data: &mut OperatorData, path: &DataKeyPath) -> OperatorExecutionErrorResult {
-        self.payload.execute(data, path)
+    pub fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        self.payload.execute(context)
+    }
+
+    pub fn apply(&self, context: &EvaluationContext) -> OperatorApplicationResult {
+        self.payload.apply(context)
+    }
+
+    pub fn source(&self) -> &OperatorSource {
+        &self.source
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// References to the templates used by this operator, or by those nested in it,
+    /// see [`native::NativeOperatorDefinition::TEMPLATES`].
+    pub fn templates(&self) -> Vec<Reference> {
+        native::template_references(&self.expr)
+    }
+}
+
+impl OperatorPriority for Operator {
+    fn priority(&self) -> OperatorPriorityRank {
+        self.priority
+    }
+}
+
+/// An argument as operators hold it, nested calls being already parsed as operators.
+#[derive(Debug, Clone)]
+pub enum OperatorArgument {
+    StringLiteral(StringLiteral),
+    NumberLiteral(NumberLiteral),
+    BooleanLiteral(BooleanLiteral),
+    Reference(Reference),
+    Operator(Operator),
+    Fallback(Vec<OperatorArgument>),
+    Keyword(Keyword),
+}
+
+impl OperatorArgument {
+    /// `position` is that of `argument` in its call, to locate errors in nested calls.
+    pub fn try_from_argument(argument: &Argument, position: usize, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        match argument {
+            Argument::StringLiteral(inner) => Ok(OperatorArgument::StringLiteral(inner.clone())),
+            Argument::NumberLiteral(inner) => Ok(OperatorArgument::NumberLiteral(inner.clone())),
+            Argument::BooleanLiteral(inner) => Ok(OperatorArgument::BooleanLiteral(inner.clone())),
+            Argument::Reference(inner) => Ok(OperatorArgument::Reference(inner.clone())),
+            Argument::Keyword(inner) => Ok(OperatorArgument::Keyword(*inner)),
+            Argument::Expr(inner) => registry.try_parsing_nested_operator(inner, position).map(OperatorArgument::Operator),
+            Argument::Fallback(alternatives) => alternatives.iter()
+                .map(|alternative| OperatorArgument::try_from_argument(alternative, position, registry))
+                .collect::<Result<_, _>>()
+                .map(OperatorArgument::Fallback),
+        }
+    }
+
+    /// Literals evaluate to themselves, keywords to their name, references to what they point to,
+    /// and nested operators to their result.
+    /// A nested operator waiting on unevaluated data makes the one holding it wait as well.
+    /// Fallbacks skip unavailable alternatives, but still wait on unevaluated ones, and fail on unbound variables.
+    pub fn evaluate(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match self {
+            OperatorArgument::StringLiteral(inner) => Ok(OperatorData::String(inner.0.clone())),
+            OperatorArgument::Keyword(inner) => Ok(OperatorData::String(inner.as_str().to_owned())),
+            OperatorArgument::BooleanLiteral(inner) => Ok(OperatorData::Boolean(inner.0)),
+            OperatorArgument::NumberLiteral(inner) => f64::try_from(inner)
+                .map(OperatorData::Number)
+                .map_err(|error| OperatorExecutionErrorReason::OtherError(format!("invalid number literal, {}", error))),
+            OperatorArgument::Reference(inner) => context.resolve(inner)
+                .map(|(_, value)| value.clone())
+                .map_err(OperatorExecutionErrorReason::ReferenceUnavailable),
+            OperatorArgument::Operator(inner) => inner.execute(context),
+            OperatorArgument::Fallback(alternatives) => {
+                let mut reasons = Vec::with_capacity(alternatives.len());
+                for alternative in alternatives {
+                    match alternative.evaluate(context) {
+                        Err(OperatorExecutionErrorReason::ReferenceUnavailable(error))
+                            if !matches!(error, ReferenceError::Unevaluated(_) | ReferenceError::UnboundVariable(_)) => {
+                            reasons.push(OperatorExecutionErrorReason::ReferenceUnavailable(error));
+                        },
+                        // Nested fallbacks are flattened, as they were alternatives of this one.
+                        Err(OperatorExecutionErrorReason::NoAlternativeAvailable(inner)) => reasons.extend(inner),
+                        result => return result,
+                    }
+                }
+                Err(OperatorExecutionErrorReason::NoAlternativeAvailable(reasons))
+            },
+        }
     }
 }
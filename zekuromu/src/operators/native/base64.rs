@@ -0,0 +1,59 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{data::OperatorData, operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}};
+
+use super::hash::canonical_bytes;
+
+native_operator! {
+    /// Standard base64, with padding, of a value serialized like hashed ones.
+    Base64Operator, "base64", Middle, { value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for Base64Operator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let bytes = canonical_bytes(self.value.evaluate(context)?)?;
+        Ok(OperatorData::String(STANDARD.encode(bytes)))
+    }
+}
+
+native_operator! {
+    /// Decodes standard base64 into a string, which must be valid UTF-8.
+    Base64DecodeOperator, "base64-decode", Middle, { value: positional(StringLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for Base64DecodeOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let encoded = match self.value.evaluate(context)? {
+            OperatorData::String(inner) => inner,
+            other => return Err(OperatorExecutionErrorReason::OtherError(format!("expected a base64 string, found {:?}", other))),
+        };
+        let bytes = STANDARD.decode(encoded.trim())
+            .map_err(|error| OperatorExecutionErrorReason::OtherError(format!("invalid base64, {}", error)))?;
+        String::from_utf8(bytes)
+            .map(OperatorData::String)
+            .map_err(|_| OperatorExecutionErrorReason::OtherError("decoded base64 is not valid UTF-8".to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_base64() {
+        let mut data = document("
+secret: abc
+encoded: (( base64 &secret ))
+decoded: (( base64-decode (base64 &secret) ))
+port: (( base64 8080 ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(string_at(&data, &["encoded"]), "YWJj");
+        assert_eq!(string_at(&data, &["decoded"]), "abc");
+        assert_eq!(string_at(&data, &["port"]), "ODA4MA==");
+
+        let mut data = document("a: (( base64-decode \"not base64!\" ))");
+        assert!(data.execute_operators().unwrap_err()[0].to_string().starts_with("at 'a': invalid base64"));
+    }
+}
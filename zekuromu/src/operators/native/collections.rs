@@ -0,0 +1,328 @@
+use std::cmp::Ordering;
+
+use crate::{data::{DataKey, OperatorData, operators::{Argument, Expr, Keyword, StringLiteral}}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::{ArgumentKind, Signature}}};
+
+use super::{NativeOperatorDefinition, check_keyword};
+
+fn evaluate_mapping(value: &OperatorArgument, context: &EvaluationContext) -> Result<Vec<(DataKey, OperatorData)>, OperatorExecutionErrorReason> {
+    match value.evaluate(context)? {
+        OperatorData::Mapping(inner) => {
+            let mut entries: Vec<_> = inner.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(entries)
+        },
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a mapping, found {:?}", other))),
+    }
+}
+
+pub(crate) fn evaluate_sequence(value: &OperatorArgument, context: &EvaluationContext) -> Result<Vec<OperatorData>, OperatorExecutionErrorReason> {
+    match value.evaluate(context)? {
+        OperatorData::Sequence(inner) => Ok(inner),
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a sequence, found {:?}", other))),
+    }
+}
+
+// Null, then booleans, numbers and strings, each compared with their own kind.
+fn scalar_rank(value: &OperatorData) -> Option<u8> {
+    match value {
+        OperatorData::Null => Some(0),
+        OperatorData::Boolean(_) => Some(1),
+        OperatorData::Number(_) => Some(2),
+        OperatorData::String(_) => Some(3),
+        _ => None,
+    }
+}
+
+/// `None` when either is not a scalar.
+pub(crate) fn compare_scalars(a: &OperatorData, b: &OperatorData) -> Option<Ordering> {
+    let ordering = match (a, b) {
+        (OperatorData::Boolean(a), OperatorData::Boolean(b)) => a.cmp(b),
+        (OperatorData::Number(a), OperatorData::Number(b)) => a.total_cmp(b),
+        (OperatorData::String(a), OperatorData::String(b)) => a.cmp(b),
+        _ => scalar_rank(a)?.cmp(&scalar_rank(b)?),
+    };
+    Some(ordering)
+}
+
+// Stable, and fails on the first value which is not a scalar.
+fn sort_by_key(mut sequence: Vec<OperatorData>, key: impl for<'a> Fn(&'a OperatorData) -> Result<&'a OperatorData, OperatorExecutionErrorReason>) -> OperatorExecutionResult {
+    for item in &sequence {
+        let key = key(item)?;
+        if scalar_rank(key).is_none() {
+            return Err(OperatorExecutionErrorReason::OtherError(format!("cannot sort by {:?}, which is not a scalar", key)))
+        }
+    }
+
+    sequence.sort_by(|a, b| match (key(a), key(b)) {
+        (Ok(a), Ok(b)) => compare_scalars(a, b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    });
+    Ok(OperatorData::Sequence(sequence))
+}
+
+native_operator! {
+    /// Keys of a mapping, sorted.
+    KeysOperator, "keys", BeforeLast, { mapping: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for KeysOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let entries = evaluate_mapping(&self.mapping, context)?;
+        Ok(OperatorData::Sequence(entries.into_iter().map(|(key, _)| OperatorData::String(key.as_str().to_owned())).collect()))
+    }
+}
+
+native_operator! {
+    /// Values of a mapping, in the order of its sorted keys.
+    ValuesOperator, "values", BeforeLast, { mapping: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for ValuesOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let entries = evaluate_mapping(&self.mapping, context)?;
+        Ok(OperatorData::Sequence(entries.into_iter().map(|(_, value)| value).collect()))
+    }
+}
+
+/// Sorts a sequence of scalars, null first, then booleans, numbers and strings, as in `(( sort &names ))`.
+/// Sequences of mappings are sorted by one of their fields, as in `(( sort by "name" &jobs ))`.
+#[derive(Debug, Clone)]
+pub struct SortOperator {
+    field: Option<StringLiteral>,
+    sequence: OperatorArgument,
+}
+
+impl OperatorPriority for SortOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::BeforeLast
+    }
+}
+
+impl NativeOperatorDefinition for SortOperator {
+    const NAME: &'static str = "sort";
+
+    fn signature() -> Signature {
+        Signature::new()
+            .positional(&[ArgumentKind::Keyword, ArgumentKind::Reference, ArgumentKind::Expr, ArgumentKind::Fallback])
+            .optional(&[ArgumentKind::StringLiteral])
+            .optional(&[ArgumentKind::Reference, ArgumentKind::Expr, ArgumentKind::Fallback])
+    }
+
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        if expr.name.0 != Self::NAME {
+            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
+        }
+
+        if let Some(Argument::Keyword(keyword)) = expr.arguments.first() {
+            check_keyword(*keyword, 0, &[Keyword::By])?;
+        }
+
+        SortOperator::signature().check(&expr.arguments)?;
+
+        // What the arguments are depends on whether the first one is `by`.
+        let (field, sequence) = match expr.arguments.as_slice() {
+            [Argument::Keyword(_), Argument::StringLiteral(field), _] => (Some(field.clone()), 2),
+            [Argument::Keyword(_), ..] => {
+                return Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum: 3, maximum: Some(3), found: expr.arguments.len() })
+            },
+            [_] => (None, 0),
+            _ => {
+                return Err(OperatorParsingErrorReason::InvalidArgument { position: 1, reason: "is only expected after by, as in (( sort by \"name\" &list ))".to_owned() })
+            },
+        };
+
+        Ok(SortOperator { field, sequence: OperatorArgument::try_from_argument(&expr.arguments[sequence], sequence, registry)? })
+    }
+}
+
+impl OperatorPayload for SortOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let sequence = evaluate_sequence(&self.sequence, context)?;
+        let Some(field) = &self.field else {
+            return sort_by_key(sequence, |item| Ok(item))
+        };
+
+        let key = DataKey::from(field.0.as_str());
+        sort_by_key(sequence, |item| match item {
+            OperatorData::Mapping(inner) => inner.get(&key)
+                .ok_or_else(|| OperatorExecutionErrorReason::OtherError(format!("an element has no field '{}'", field.0))),
+            other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a mapping to sort by '{}', found {:?}", field.0, other))),
+        })
+    }
+}
+
+// Scalars as they would be written in a reference.
+fn scalar_string(value: &OperatorData) -> Result<String, OperatorExecutionErrorReason> {
+    match value {
+        OperatorData::Null => Ok("null".to_owned()),
+        OperatorData::Boolean(inner) => Ok(inner.to_string()),
+        OperatorData::Number(inner) => Ok(inner.to_string()),
+        OperatorData::String(inner) => Ok(inner.clone()),
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a scalar, found {:?}", other))),
+    }
+}
+
+native_operator! {
+    /// Every combination of one element of each sequence, joined into a string, as in
+    /// `(( cartesian-product &regions "-" &tiers ))`, the first sequence varying the slowest.
+    /// Scalars are taken as sequences of a single element.
+    CartesianProductOperator, "cartesian-product", BeforeLast, {
+        first: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+        rest: variadic(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for CartesianProductOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let mut products = vec![String::new()];
+        for argument in std::iter::once(&self.first).chain(&self.rest) {
+            let elements = match argument.evaluate(context)? {
+                OperatorData::Sequence(inner) => inner.iter().map(scalar_string).collect::<Result<Vec<_>, _>>()?,
+                other => vec![scalar_string(&other)?],
+            };
+            products = products.iter()
+                .flat_map(|product| elements.iter().map(move |element| format!("{}{}", product, element)))
+                .collect();
+        }
+        Ok(OperatorData::Sequence(products.into_iter().map(OperatorData::String).collect()))
+    }
+}
+
+fn flatten_into(sequence: Vec<OperatorData>, flattened: &mut Vec<OperatorData>) {
+    for item in sequence {
+        match item {
+            OperatorData::Sequence(inner) => flatten_into(inner, flattened),
+            other => flattened.push(other),
+        }
+    }
+}
+
+native_operator! {
+    /// Flattens nested sequences, however deep, into a single one.
+    FlattenOperator, "flatten", BeforeLast, { sequence: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for FlattenOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let mut flattened = vec![];
+        flatten_into(evaluate_sequence(&self.sequence, context)?, &mut flattened);
+        Ok(OperatorData::Sequence(flattened))
+    }
+}
+
+native_operator! {
+    /// Keeps the first of elements which are deeply equal.
+    UniqueOperator, "unique", BeforeLast, { sequence: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for UniqueOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let mut unique: Vec<OperatorData> = vec![];
+        for item in evaluate_sequence(&self.sequence, context)? {
+            if !unique.iter().any(|kept| kept.deep_equals(&item)) {
+                unique.push(item);
+            }
+        }
+        Ok(OperatorData::Sequence(unique))
+    }
+}
+
+native_operator! {
+    /// Keeps the mappings whose field holds a value, as in `(( filter &jobs "tier" "web" ))`.
+    /// A string value matches scalars written the same way, as in references.
+    FilterOperator, "filter", BeforeLast, {
+        sequence: positional(Reference | Expr | Fallback),
+        field: positional(StringLiteral),
+        value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for FilterOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let field = DataKey::from(self.field.0.as_str());
+        let value = self.value.evaluate(context)?;
+        let matches = |candidate: &OperatorData| match &value {
+            OperatorData::String(inner) => candidate.matches_scalar(inner),
+            other => candidate.deep_equals(other),
+        };
+
+        let filtered = evaluate_sequence(&self.sequence, context)?.into_iter()
+            .filter(|item| match item {
+                OperatorData::Mapping(inner) => inner.get(&field).is_some_and(matches),
+                _ => false,
+            })
+            .collect();
+        Ok(OperatorData::Sequence(filtered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_collections() {
+        let mut data = document("
+services:
+  web: {port: 80}
+  api: {port: 8080}
+names: (( keys &services ))
+ports: (( values &services ))
+mixed: (( sort &unsorted ))
+unsorted: [b, 10, a, 2, true, null]
+jobs: (( sort by \"port\" (values &services) ))
+by-name: (( sort by \"name\" &named ))
+named:
+  - {name: b, id: 1}
+  - {name: a, id: 2}
+  - {name: b, id: 3}
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(yaml_at(&data, &["names"]), "- api\n- web\n");
+        assert_eq!(yaml_at(&data, &["ports"]), "- port: 8080\n- port: 80\n");
+        assert_eq!(yaml_at(&data, &["mixed"]), "- null\n- true\n- 2\n- 10\n- a\n- b\n");
+        assert_eq!(yaml_at(&data, &["jobs"]), "- port: 80\n- port: 8080\n");
+        // Stable, `b` keeping its order.
+        assert_eq!(yaml_at(&data, &["by-name"]), "- id: 2\n  name: a\n- id: 1\n  name: b\n- id: 3\n  name: b\n");
+
+        let mut data = document("a: (( sort &b ))\nb: [[1], 2]\nc: (( keys &b ))\nd: (( sort by \"x\" &e ))\ne: [{x: 1}, {y: 2}]");
+        let errors = reasons(data.execute_operators().unwrap_err());
+        assert_eq!(errors.len(), 3);
+
+        assert!(parsing_error("a: (( sort by &b ))").starts_with("at 'a': sort: argument 2 expected string literal, found reference &b"));
+        assert!(parsing_error("a: (( sort by \"x\" ))").starts_with("at 'a': sort: expected 3 arguments, found 2"));
+        assert!(parsing_error("a: (( sort hash &b ))").starts_with("at 'a': sort: argument 1 expected by, found hash"));
+        assert!(parsing_error("a: (( sort &b \"x\" &c ))").starts_with("at 'a': sort: argument 2 is only expected after by, as in (( sort by \"name\" &list ))"));
+    }
+
+    #[test]
+    fn test_sequence_operators() {
+        let mut data = document("
+regions: [eu, us]
+tiers: (( grab &defaults.tiers ))
+defaults:
+  tiers: [web, 1]
+matrix: (( cartesian-product &regions \"-\" &tiers ))
+lists: [[a, [b]], c, ['(( grab &regions[0] ))']]
+flat: (( flatten &lists ))
+unique: (( unique (flatten &lists) ))
+duplicated: [{a: 1}, {a: 1}, {a: 2}, 1, \"1\"]
+deduplicated: (( unique &duplicated ))
+jobs:
+  - {name: a, port: 80, web: true}
+  - {name: b, port: 8080}
+  - {name: c, port: 80}
+on-80: (( filter &jobs \"port\" 80 ))
+web: (( filter &jobs \"web\" \"true\" ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(yaml_at(&data, &["matrix"]), "- eu-web\n- eu-1\n- us-web\n- us-1\n");
+        assert_eq!(yaml_at(&data, &["flat"]), "- a\n- b\n- c\n- eu\n");
+        assert_eq!(yaml_at(&data, &["unique"]), "- a\n- b\n- c\n- eu\n");
+        assert_eq!(yaml_at(&data, &["deduplicated"]), "- a: 1\n- a: 2\n- 1\n- '1'\n");
+        assert_eq!(yaml_at(&data, &["on-80"]), "- name: a\n  port: 80\n  web: true\n- name: c\n  port: 80\n");
+        assert_eq!(yaml_at(&data, &["web"]), "- name: a\n  port: 80\n  web: true\n");
+    }
+}
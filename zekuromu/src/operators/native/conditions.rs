@@ -0,0 +1,203 @@
+use std::cmp::Ordering;
+
+use crate::{data::OperatorData, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}};
+
+use super::collections::compare_scalars;
+
+fn evaluate_boolean(argument: &OperatorArgument, context: &EvaluationContext) -> Result<bool, OperatorExecutionErrorReason> {
+    match argument.evaluate(context)? {
+        OperatorData::Boolean(inner) => Ok(inner),
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a boolean, found {:?}", other))),
+    }
+}
+
+// Only numbers with numbers, and strings with strings, have an order.
+fn compare(left: &OperatorArgument, right: &OperatorArgument, context: &EvaluationContext) -> Result<Ordering, OperatorExecutionErrorReason> {
+    match (left.evaluate(context)?, right.evaluate(context)?) {
+        (left @ OperatorData::Number(_), right @ OperatorData::Number(_))
+        | (left @ OperatorData::String(_), right @ OperatorData::String(_)) => Ok(compare_scalars(&left, &right).expect("both are scalars")),
+        (left, right) => Err(OperatorExecutionErrorReason::OtherError(format!("cannot compare {:?} with {:?}", left, right))),
+    }
+}
+
+native_operator! {
+    /// The second argument when the condition holds, the third otherwise, as in `(( ternary (eq &env "prod") 3 1 ))`.
+    /// Only the chosen branch is evaluated, so the other may refer to something unavailable.
+    TernaryOperator, "ternary", Middle, {
+        condition: positional(Reference | Expr | Fallback),
+        then: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+        otherwise: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for TernaryOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match evaluate_boolean(&self.condition, context)? {
+            true => self.then.evaluate(context),
+            false => self.otherwise.evaluate(context),
+        }
+    }
+}
+
+native_operator! {
+    /// Whether both values are deeply equal, numbers never being equal to strings.
+    EqOperator, "eq", Middle, {
+        left: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+        right: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for EqOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Boolean(self.left.evaluate(context)?.deep_equals(&self.right.evaluate(context)?)))
+    }
+}
+
+native_operator! {
+    /// Whether both values differ, the opposite of `eq`.
+    NeOperator, "ne", Middle, {
+        left: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+        right: positional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for NeOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Boolean(!self.left.evaluate(context)?.deep_equals(&self.right.evaluate(context)?)))
+    }
+}
+
+native_operator! {
+    /// Whether the first value is less than the second, both being numbers or both strings.
+    LtOperator, "lt", Middle, {
+        left: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+        right: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for LtOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Boolean(compare(&self.left, &self.right, context)? == Ordering::Less))
+    }
+}
+
+native_operator! {
+    /// Whether the first value is greater than the second, both being numbers or both strings.
+    GtOperator, "gt", Middle, {
+        left: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+        right: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for GtOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Boolean(compare(&self.left, &self.right, context)? == Ordering::Greater))
+    }
+}
+
+native_operator! {
+    /// Whether a value is an element of a sequence, a key of a mapping, or a substring of a string.
+    InOperator, "in", Middle, {
+        value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+        container: positional(StringLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for InOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = self.value.evaluate(context)?;
+        let found = match (self.container.evaluate(context)?, &value) {
+            (OperatorData::Sequence(inner), _) => inner.iter().any(|item| item.deep_equals(&value)),
+            (OperatorData::Mapping(inner), OperatorData::String(key)) => inner.keys().any(|candidate| candidate.as_str() == key),
+            (OperatorData::String(inner), OperatorData::String(part)) => inner.contains(part.as_str()),
+            (container, _) => return Err(OperatorExecutionErrorReason::OtherError(format!("cannot look for {:?} in {:?}", value, container))),
+        };
+        Ok(OperatorData::Boolean(found))
+    }
+}
+
+native_operator! {
+    /// Whether every condition holds, stopping at the first which does not.
+    AndOperator, "and", Middle, {
+        first: positional(Reference | Expr | Fallback),
+        rest: variadic(Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for AndOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        for condition in std::iter::once(&self.first).chain(&self.rest) {
+            if !evaluate_boolean(condition, context)? {
+                return Ok(OperatorData::Boolean(false))
+            }
+        }
+        Ok(OperatorData::Boolean(true))
+    }
+}
+
+native_operator! {
+    /// Whether any condition holds, stopping at the first which does.
+    OrOperator, "or", Middle, {
+        first: positional(Reference | Expr | Fallback),
+        rest: variadic(Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for OrOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        for condition in std::iter::once(&self.first).chain(&self.rest) {
+            if evaluate_boolean(condition, context)? {
+                return Ok(OperatorData::Boolean(true))
+            }
+        }
+        Ok(OperatorData::Boolean(false))
+    }
+}
+
+native_operator! {
+    /// The opposite of a condition.
+    NotOperator, "not", Middle, { condition: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for NotOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Boolean(!evaluate_boolean(&self.condition, context)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_conditions() {
+        let mut data = document(r#"
+env: prod
+regions: [eu, us]
+limits: {cpu: 4}
+replicas: (( ternary (eq &env "prod") 3 1 ))
+untaken: (( ternary (ne &env "prod") &missing.key "fine" ))
+later: (( ternary (in "eu" &regions) (grab &base) 0 ))
+base: (( grab &limits.cpu ))
+logic: (( and (not (eq &env "dev")) (or (gt &limits.cpu 8) (lt "a" "b")) ))
+short-circuit: (( or (in "cpu" &limits) &missing ))
+substring: (( in "ro" &env ))
+mixed: (( lt 1 "2" ))
+not-boolean: (( ternary &env 1 2 ))
+production: (( ternary (eq &env "prod") true false ))
+flagged: (( eq (ne &env "dev") true ))
+"#);
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        let mut failed: Vec<_> = errors.iter().map(|(at, _)| at.clone()).collect();
+        failed.sort_by_key(|at| at.to_string());
+        assert_eq!(failed, vec![path(&["mixed"]), path(&["not-boolean"])]);
+        assert_eq!(number_at(&data, &["replicas"]), 3.0);
+        assert_eq!(number_at(&data, &["later"]), 4.0);
+        assert_eq!(string_at(&data, &["untaken"]), "fine");
+        for key in ["logic", "short-circuit", "substring", "production", "flagged"] {
+            assert!(matches!(data.get(&path(&[key])), Some(OperatorData::Boolean(true))), "{}", key);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{data::{OperatorData, operators::{Argument, DEFER_KEYWORD, Expr}}, operators::{EvaluationContext, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::{ArgumentKind, Signature}}};
+
+use super::NativeOperatorDefinition;
+
+/// Writes its call back as an operator, as in `(( defer grab &a ))` giving `(( grab &a ))`, for a later pass.
+/// The deferred call is kept as written, not parsed, as its operator may only exist in that later pass.
+#[derive(Debug, Clone)]
+pub struct DeferOperator {
+    deferred: Expr,
+}
+
+impl DeferOperator {
+    pub fn deferred(&self) -> &Expr {
+        &self.deferred
+    }
+}
+
+impl OperatorPriority for DeferOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::AfterFirst
+    }
+}
+
+impl NativeOperatorDefinition for DeferOperator {
+    const NAME: &'static str = DEFER_KEYWORD;
+
+    fn signature() -> Signature {
+        Signature::new().positional(&[ArgumentKind::Expr])
+    }
+
+    fn try_from_expr(expr: &Expr, _registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        if expr.name.0 != DEFER_KEYWORD {
+            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
+        }
+
+        DeferOperator::signature().check(&expr.arguments)?;
+
+        match &expr.arguments[0] {
+            Argument::Expr(inner) => Ok(DeferOperator { deferred: inner.as_ref().clone() }),
+            _ => unreachable!("argument kinds are checked by the signature"),
+        }
+    }
+}
+
+impl OperatorPayload for DeferOperator {
+    fn execute(&self, _context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::String(self.deferred.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_defer() {
+        let mut data = document("
+tenant: (( defer grab &tenant.name || \"default\" ))
+later: (( defer unknown (grab &a) ))
+a: (( defer defer param ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(string_at(&data, &["tenant"]), "(( grab &tenant.name || \"default\" ))");
+        assert_eq!(string_at(&data, &["later"]), "(( unknown (grab &a) ))");
+        assert_eq!(string_at(&data, &["a"]), "(( defer param ))");
+    }
+}
@@ -0,0 +1,107 @@
+use std::{collections::HashMap, env::VarError, rc::Rc};
+
+use crate::{data::OperatorData, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload, registry::OperatorRegistry}};
+
+native_operator! {
+    /// Reads an environment variable, as in `(( env "PORT" 8080 ))`, from the registry's environment when it sets one.
+    /// The value is read as a number or a boolean when the default is such a literal, and as a string otherwise.
+    /// The default is only evaluated when the variable is not set.
+    EnvOperator, "env", Middle, {
+        name: positional(StringLiteral),
+        default: optional(StringLiteral | NumberLiteral | BooleanLiteral | Reference | Expr | Fallback),
+    }, from registry {
+        /// `None` for the process environment.
+        variables: Option<Rc<HashMap<String, String>>> = OperatorRegistry::environment,
+    }
+}
+
+impl EnvOperator {
+    fn read(&self) -> Result<String, VarError> {
+        match &self.variables {
+            Some(variables) => variables.get(&self.name.0).cloned().ok_or(VarError::NotPresent),
+            None => std::env::var(&self.name.0),
+        }
+    }
+
+    // Read as the literal type of the default.
+    fn coerce(&self, value: String, default: &OperatorArgument) -> OperatorExecutionResult {
+        let invalid = |expected: &str| OperatorExecutionErrorReason::OtherError(
+            format!("environment variable '{}' is set to '{}', which is not {}", self.name.0, value, expected)
+        );
+        match default {
+            OperatorArgument::NumberLiteral(_) => value.trim().parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(OperatorData::Number)
+                .ok_or_else(|| invalid("a number")),
+            OperatorArgument::BooleanLiteral(_) => match value.trim().to_ascii_lowercase().as_str() {
+                "true" => Ok(OperatorData::Boolean(true)),
+                "false" => Ok(OperatorData::Boolean(false)),
+                _ => Err(invalid("a boolean")),
+            },
+            _ => Ok(OperatorData::String(value)),
+        }
+    }
+}
+
+impl OperatorPayload for EnvOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match (self.read(), &self.default) {
+            (Ok(value), Some(default)) => self.coerce(value, default),
+            (Ok(value), None) => Ok(OperatorData::String(value)),
+            (Err(VarError::NotPresent), Some(default)) => default.evaluate(context),
+            (Err(VarError::NotPresent), None) => Err(OperatorExecutionErrorReason::OtherError(
+                format!("environment variable '{}' is not set, and has no default", self.name.0)
+            )),
+            (Err(VarError::NotUnicode(_)), _) => Err(OperatorExecutionErrorReason::OtherError(
+                format!("environment variable '{}' is not valid unicode", self.name.0)
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{operators::native::NativeOperator, test_helpers::*};
+
+    fn document_in(environment: &[(&str, &str)], yaml: &str) -> OperatorData {
+        let mut registry = OperatorRegistry::new();
+        registry.set_environment(environment.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect());
+        raw_document(yaml).into_raw_operator_data().try_into_operator_data(&registry).unwrap()
+    }
+
+    #[test]
+    fn test_env() {
+        let environment = [("PORT", "9090"), ("DEBUG", "True"), ("NAME", "web")];
+        let mut data = document_in(&environment, "
+port: (( env \"PORT\" 8080 ))
+debug: (( env \"DEBUG\" false ))
+untyped: (( env \"PORT\" &nowhere ))
+name: (( env \"NAME\" ))
+missing: (( env \"MISSING\" 12 ))
+process: (( env \"PATH\" \"unset\" ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(number_at(&data, &["port"]), 9090.0);
+        assert_eq!(number_at(&data, &["missing"]), 12.0);
+        assert!(matches!(data.get(&path(&["debug"])), Some(OperatorData::Boolean(true))));
+        assert_eq!(string_at(&data, &["name"]), "web");
+        // The default is not evaluated when the variable is set.
+        assert_eq!(string_at(&data, &["untyped"]), "9090");
+        // The process environment is not read once the registry sets one.
+        assert_eq!(string_at(&data, &["process"]), "unset");
+
+        let mut data = document_in(&environment, "a: (( env \"NAME\" 1 ))\nb: (( env \"MISSING\" ))\nc: (( env \"MISSING\" &nowhere ))");
+        let errors = data.execute_operators().unwrap_err();
+        assert_eq!(errors[0].to_string(), "at 'a': environment variable 'NAME' is set to 'web', which is not a number");
+        assert_eq!(errors[1].to_string(), "at 'b': environment variable 'MISSING' is not set, and has no default");
+        assert_eq!(errors.len(), 3);
+
+        let mut registry = OperatorRegistry::new();
+        registry.disable(NativeOperator::Env);
+        let error = raw_document("a: (( grab &b || (env \"HOME\") ))").into_raw_operator_data().try_into_operator_data(&registry).unwrap_err();
+        assert_eq!(error.to_string(), "at 'a': grab: argument 1, env: operator is disabled\n(( grab &b || (env \"HOME\") ))\n               ^^^");
+    }
+}
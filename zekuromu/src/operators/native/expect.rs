@@ -1,40 +1,13 @@
-use crate::{data::{DataKeyPath, OperatorData, operators::{Argument, Expr, StringLiteral}}, operators::{OperatorExecutionErrorReason, OperatorExecutionErrorResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank}};
+use crate::operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload};
 
-#[derive(Debug, Clone)]
-pub struct ExpectOperator {
-    error_msg: StringLiteral
-}
-
-impl TryFrom<&Expr> for ExpectOperator {
-    type Error = OperatorParsingErrorReason;
-
-    fn try_from(value: &Expr) -> Result<Self, Self::Error> {
-        if value.name.0 != "expect" {
-            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
-        }
-
-        if value.arguments.len() != 1 {
-            return Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch)
-        }
-
-        let first_arg = value.arguments.get(0).unwrap();
-        match first_arg {
-            Argument::StringLiteral(inner) => Ok(ExpectOperator { error_msg: inner.clone() }),
-            _ => Err(OperatorParsingErrorReason::ArgumentsTypesDoNotMatch)
-        }
-    }
+native_operator! {
+    ExpectOperator, "expect", Last, { error_msg: positional(StringLiteral) }
 }
 
 impl OperatorPayload for ExpectOperator {
-    fn execute(&self, _data: &mut OperatorData, path: &DataKeyPath) -> OperatorExecutionErrorResult {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
         Err(OperatorExecutionErrorReason::OtherError(
-            format!("At path '{}', expected a value after operator execution. Message: '{}'", path, self.error_msg.0))
+            format!("At path '{}', expected a value after operator execution. Message: '{}'", context.path, self.error_msg.0))
         )
     }
 }
-
-impl OperatorPriority for ExpectOperator {
-    fn priority(&self) -> OperatorPriorityRank {
-        OperatorPriorityRank::Last
-    }
-}
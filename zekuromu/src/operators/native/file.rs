@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use crate::{data::{OperatorData, operators::Expr}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::{ArgumentKind, Signature}}};
+
+use super::NativeOperatorDefinition;
+
+/// Where the operators reading files find them.
+#[derive(Debug, Clone)]
+pub(crate) struct FileLocation {
+    /// The directory of the file holding the operator, if it was read from one.
+    base: Option<PathBuf>,
+    root: Option<PathBuf>,
+}
+
+impl FileLocation {
+    pub(crate) fn of(expr: &Expr, registry: &OperatorRegistry) -> Self {
+        FileLocation {
+            base: expr.location.origin.as_deref().and_then(Path::parent).map(Path::to_path_buf),
+            root: registry.file_root().map(Path::to_path_buf),
+        }
+    }
+
+    /// Relative paths are relative to the file holding the operator, or to the root for data read from elsewhere,
+    /// and every path must lie under the root.
+    /// The path returned is canonical, so that the file read is the one checked, symbolic links included.
+    pub(crate) fn resolve(&self, path: &OperatorArgument, context: &EvaluationContext) -> Result<PathBuf, OperatorExecutionErrorReason> {
+        let path = match path.evaluate(context)? {
+            OperatorData::String(inner) => PathBuf::from(inner),
+            other => return Err(OperatorExecutionErrorReason::OtherError(format!("expected a path, found {:?}", other))),
+        };
+        let path = match self.base.as_ref().or(self.root.as_ref()) {
+            Some(base) if path.is_relative() => base.join(path),
+            _ => path,
+        };
+
+        let unavailable = |reason: String| OperatorExecutionErrorReason::FileUnavailable { path: path.clone(), reason };
+        let canonical = path.canonicalize()
+            .map_err(|error| unavailable(error.to_string()))?;
+        if let Some(root) = &self.root {
+            let canonical_root = root.canonicalize()
+                .map_err(|error| unavailable(format!("root directory '{}' is unavailable, {}", root.display(), error)))?;
+            if !canonical.starts_with(&canonical_root) {
+                return Err(unavailable(format!("it lies outside of '{}'", root.display())))
+            }
+        }
+
+        Ok(canonical)
+    }
+}
+
+/// Parses the operators taking a path as their only argument, `file` and `load`.
+pub(crate) fn try_parsing_path_operator(name: &str, expr: &Expr, registry: &OperatorRegistry) -> Result<(OperatorArgument, FileLocation), OperatorParsingErrorReason> {
+    if expr.name.0 != name {
+        return Err(OperatorParsingErrorReason::NameDoesNotMatch)
+    }
+
+    path_signature().check(&expr.arguments)?;
+
+    let path = OperatorArgument::try_from_argument(&expr.arguments[0], 0, registry)?;
+    Ok((path, FileLocation::of(expr, registry)))
+}
+
+pub(crate) fn path_signature() -> Signature {
+    Signature::new().positional(&[ArgumentKind::StringLiteral, ArgumentKind::Reference, ArgumentKind::Expr])
+}
+
+/// Inlines the content of a file as a string, as in `(( file "certs/ca.pem" ))`.
+/// Relative paths are relative to the file holding the operator.
+#[derive(Debug, Clone)]
+pub struct FileOperator {
+    path: OperatorArgument,
+    location: FileLocation,
+}
+
+impl OperatorPriority for FileOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::Middle
+    }
+}
+
+impl NativeOperatorDefinition for FileOperator {
+    const NAME: &'static str = "file";
+
+    fn signature() -> Signature {
+        path_signature()
+    }
+
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        let (path, location) = try_parsing_path_operator(Self::NAME, expr, registry)?;
+        Ok(FileOperator { path, location })
+    }
+}
+
+impl OperatorPayload for FileOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let file = self.location.resolve(&self.path, context)?;
+        std::fs::read_to_string(&file)
+            .map(OperatorData::String)
+            .map_err(|error| OperatorExecutionErrorReason::FileUnavailable { path: file, reason: error.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_file_and_load() {
+        let directory = std::env::temp_dir().join(format!("zekuromu-test-file-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("jail/shared")).unwrap();
+        std::fs::write(directory.join("jail/ca.pem"), "certificate\n").unwrap();
+        std::fs::write(directory.join("jail/shared/networks.yml"), "cidr: 10.0.0.0/8\nkept: (( grab &cidr ))\n(( replace-subtree )): ~\n").unwrap();
+        std::fs::write(directory.join("jail/shared/zones.json"), "{\"zones\": [1, 2]}").unwrap();
+        std::fs::write(directory.join("outside.txt"), "secret").unwrap();
+
+        let read = |yaml: &str, file: &str| raw_document(yaml).into_raw_operator_data_from(&directory.join(file));
+        let main = read("
+ca: (( file \"ca.pem\" ))
+networks: (( load \"shared/networks.yml\" ))
+zones: (( grab &networks.cidr ))
+escape: (( file \"../outside.txt\" ))
+", "jail/main.yml");
+        let overrides = read("escape: (( load \"zones.json\" ))", "jail/shared/overrides.yml");
+
+        let mut registry = OperatorRegistry::new();
+        registry.set_file_root(directory.join("jail"));
+        let mut data = main.clone().recursive_merge(overrides).try_into_operator_data(&registry).unwrap();
+        data.execute_operators().unwrap();
+
+        assert_eq!(string_at(&data, &["ca"]), "certificate\n");
+        assert_eq!(string_at(&data, &["zones"]), "10.0.0.0/8");
+        // Operators of loaded files are not executed.
+        assert_eq!(string_at(&data, &["networks", "kept"]), "(( grab &cidr ))");
+        assert_eq!(yaml_at(&data, &["networks"]), "cidr: 10.0.0.0/8\nkept: (( grab &cidr ))\n");
+        assert_eq!(number_at(&data, &["escape", "zones", "1"]), 2.0);
+
+        let mut data = main.try_into_operator_data(&registry).unwrap();
+        let errors = reasons(data.execute_operators().unwrap_err());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            &errors[0],
+            (at, OperatorExecutionErrorReason::FileUnavailable { reason, .. }) if *at == path(&["escape"]) && reason.starts_with("it lies outside of")
+        ));
+
+        // Links are followed before checking where the file lies.
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(directory.join("outside.txt"), directory.join("jail/link.txt")).unwrap();
+            let mut data = read("linked: (( file \"link.txt\" ))", "jail/main.yml").try_into_operator_data(&registry).unwrap();
+            let errors = reasons(data.execute_operators().unwrap_err());
+            assert!(matches!(
+                &errors[..],
+                [(at, OperatorExecutionErrorReason::FileUnavailable { reason, .. })] if *at == path(&["linked"]) && reason.starts_with("it lies outside of")
+            ));
+        }
+
+        // Data read from elsewhere, such as stdin, finds files under the root.
+        let mut data = raw_document("ca: (( file \"ca.pem\" ))").into_raw_operator_data().try_into_operator_data(&registry).unwrap();
+        data.execute_operators().unwrap();
+        assert_eq!(string_at(&data, &["ca"]), "certificate\n");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
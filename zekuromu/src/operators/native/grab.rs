@@ -1,37 +1,18 @@
-use crate::{data::{DataKeyPath, OperatorData, operators::{Argument, Expr, Reference}}, operators::{OperatorExecutionErrorReason, OperatorExecutionErrorResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank}};
+use crate::operators::{EvaluationContext, OperatorArgument, OperatorExecutionResult, OperatorPayload};
 
-#[derive(Debug, Clone)]
-pub struct GrabOperator {
-    reference: Reference
+native_operator! {
+    /// Copies the value of a reference, or of the first available one in `&a || &b || "default"`.
+    GrabOperator, "grab", AfterFirst, { value: positional(Reference | Fallback) }
 }
 
-impl TryFrom<&Expr> for GrabOperator {
-    type Error = OperatorParsingErrorReason;
-
-    fn try_from(value: &Expr) -> Result<Self, Self::Error> {
-        if &value.name.0 != "grab" {
-            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
-        }
-
-        if value.arguments.len() != 1 {
-            return Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch)
-        }
-
-        let first_arg = value.arguments.get(0).unwrap();
-        match first_arg {
-            Argument::Reference(inner) => Ok(GrabOperator { reference: inner.clone() }),
-            _ => Err(OperatorParsingErrorReason::ArgumentsTypesDoNotMatch)
-        }    }
-}
-
-impl OperatorPayload for GrabOperator {
-    fn execute(&self, data: &mut OperatorData, path: &DataKeyPath) -> OperatorExecutionErrorResult {
-        Err(OperatorExecutionErrorReason::Unimplemented)
+impl GrabOperator {
+    pub fn value(&self) -> &OperatorArgument {
+        &self.value
     }
 }
 
-impl OperatorPriority for GrabOperator {
-    fn priority(&self) -> OperatorPriorityRank {
-        OperatorPriorityRank::AfterFirst
+impl OperatorPayload for GrabOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        self.value.evaluate(context)
     }
 }
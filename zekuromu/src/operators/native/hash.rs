@@ -0,0 +1,79 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{data::{OperatorData, RawData}, operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}, raw};
+
+/// Strings are taken as is, other scalars as written in YAML without a line break,
+/// and collections as their YAML serialization, whose keys are sorted.
+/// Hashes of mappings thus do not depend on the order their keys were written in.
+pub(crate) fn canonical_bytes(value: OperatorData) -> Result<Vec<u8>, OperatorExecutionErrorReason> {
+    match value {
+        OperatorData::String(inner) => Ok(inner.into_bytes()),
+        other => {
+            let raw = RawData::try_from(other)
+                .map_err(|_| OperatorExecutionErrorReason::OtherError("value still holds operators".to_owned()))?;
+            let mut written = raw::serialize_raw_data_as_yaml(&raw)
+                .map_err(OperatorExecutionErrorReason::OtherError)?;
+            if !matches!(raw, RawData::Sequence(_) | RawData::Mapping(_)) {
+                written.truncate(written.trim_end_matches('\n').len());
+            }
+            Ok(written.into_bytes())
+        },
+    }
+}
+
+fn hex_digest<D: Digest>(value: OperatorData) -> OperatorExecutionResult {
+    let digest = D::digest(canonical_bytes(value)?);
+    Ok(OperatorData::String(digest.iter().map(|byte| format!("{:02x}", byte)).collect()))
+}
+
+native_operator! {
+    /// Hexadecimal SHA-256 of a value, as in `(( sha256 &config ))`.
+    Sha256Operator, "sha256", Middle, { value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for Sha256Operator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        hex_digest::<Sha256>(self.value.evaluate(context)?)
+    }
+}
+
+native_operator! {
+    /// Hexadecimal SHA-1 of a value, as in `(( sha1 &config ))`.
+    Sha1Operator, "sha1", Middle, { value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for Sha1Operator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        hex_digest::<Sha1>(self.value.evaluate(context)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_hashes() {
+        let mut data = document("
+secret: abc
+sha256: (( sha256 &secret ))
+sha1: (( sha1 \"abc\" ))
+port-hash: (( sha1 8080 ))
+port-string-hash: (( sha1 \"8080\" ))
+config:
+  b: [1, two]
+  a: true
+config-hash: (( sha256 &config ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(string_at(&data, &["sha256"]), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(string_at(&data, &["sha1"]), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(string_at(&data, &["port-hash"]), string_at(&data, &["port-string-hash"]));
+
+        let mut reordered = document("config: {a: true, b: [1, two]}\nconfig-hash: (( sha256 &config ))");
+        reordered.execute_operators().unwrap();
+        assert_eq!(string_at(&data, &["config-hash"]), string_at(&reordered, &["config-hash"]));
+    }
+}
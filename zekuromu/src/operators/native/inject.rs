@@ -0,0 +1,56 @@
+use crate::{data::OperatorData, operators::{EvaluationContext, OperatorApplicationResult, OperatorEffect, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}};
+
+native_operator! {
+    /// Merges a mapping into the one holding the operator, whose key is then removed, as in
+    /// `(( inject &meta.defaults ))`. Keys next to the operator win over injected ones.
+    /// Executed first, so that injected keys can be referenced by other operators.
+    InjectOperator, "inject", First, { mapping: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for InjectOperator {
+    /// The injected mapping, when nested in another operator.
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match self.mapping.evaluate(context)? {
+            OperatorData::Mapping(inner) => Ok(OperatorData::Mapping(inner)),
+            other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a mapping to inject, found {:?}", other))),
+        }
+    }
+
+    fn apply(&self, context: &EvaluationContext) -> OperatorApplicationResult {
+        match self.execute(context)? {
+            OperatorData::Mapping(inner) => Ok(OperatorEffect::MergeIntoParent(inner)),
+            _ => unreachable!("only mappings are executed to"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_inject() {
+        let mut data = document("
+meta:
+  defaults:
+    timeout: 30
+    resources: {cpu: 1, memory: 512}
+jobs:
+  web:
+    defaults: (( inject &meta.defaults ))
+    timeout: 60
+    resources: {memory: 1024}
+timeout: (( grab &jobs.web.timeout ))
+cpu: (( grab &jobs.web.resources.cpu ))
+");
+        data.execute_operators().unwrap();
+
+        assert_eq!(yaml_at(&data, &["jobs", "web"]), "resources:\n  cpu: 1\n  memory: 1024\ntimeout: 60\n");
+        assert_eq!(number_at(&data, &["timeout"]), 60.0);
+        assert_eq!(number_at(&data, &["cpu"]), 1.0);
+
+        let mut data = document("a: [(( inject &b ))]\nb: {c: 1}");
+        let errors = data.execute_operators().unwrap_err();
+        assert_eq!(errors[0].to_string(), "at 'a.0': can only be the value of a key in a mapping");
+    }
+}
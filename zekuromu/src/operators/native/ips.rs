@@ -0,0 +1,159 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{data::{OperatorData, operators::as_exact_integer}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}};
+
+fn invalid(message: String) -> OperatorExecutionErrorReason {
+    OperatorExecutionErrorReason::OtherError(message)
+}
+
+/// An address, and the network it lies in when given as a CIDR.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Base {
+    address: u128,
+    is_v6: bool,
+    /// First and last addresses of the network.
+    network: Option<(u128, u128)>,
+}
+
+impl Base {
+    fn parse(text: &str) -> Result<Base, OperatorExecutionErrorReason> {
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (text, None),
+        };
+        let address: IpAddr = address.trim().parse()
+            .map_err(|_| invalid(format!("'{}' is not an IP address or a CIDR", text)))?;
+        let (address, is_v6, width) = match address {
+            IpAddr::V4(inner) => (u32::from(inner) as u128, false, 32),
+            IpAddr::V6(inner) => (u128::from(inner), true, 128),
+        };
+
+        let network = prefix.map(|prefix| match prefix.trim().parse::<u32>() {
+            Ok(prefix) if prefix <= width => {
+                let size = 1u128.checked_shl(width - prefix).unwrap_or(0).wrapping_sub(1);
+                Ok((address & !size, address | size))
+            },
+            _ => Err(invalid(format!("'{}' is not a valid prefix length for '{}'", prefix, text))),
+        }).transpose()?;
+
+        Ok(Base { address, is_v6, network })
+    }
+
+    fn max(&self) -> u128 {
+        if self.is_v6 { u128::MAX } else { u32::MAX as u128 }
+    }
+
+    /// Negative offsets count from the end of the network, `-1` being its last address.
+    fn offset(&self, offset: i128) -> Result<u128, OperatorExecutionErrorReason> {
+        let out_of_range = || invalid(format!("offset {} is out of range", offset));
+        let address = match (offset < 0, self.network) {
+            (false, _) => self.address.checked_add(offset as u128),
+            (true, Some((_, last))) => last.checked_sub(offset.unsigned_abs() - 1),
+            (true, None) => return Err(invalid("negative offsets need a CIDR".to_owned())),
+        }.filter(|address| *address <= self.max()).ok_or_else(out_of_range)?;
+
+        match self.network {
+            Some((first, last)) if address < first || address > last => Err(out_of_range()),
+            _ => Ok(address),
+        }
+    }
+
+    fn format(&self, address: u128) -> String {
+        if self.is_v6 {
+            Ipv6Addr::from(address).to_string()
+        } else {
+            Ipv4Addr::from(address as u32).to_string()
+        }
+    }
+}
+
+fn evaluate_integer(argument: &OperatorArgument, context: &EvaluationContext) -> Result<i128, OperatorExecutionErrorReason> {
+    match argument.evaluate(context)? {
+        OperatorData::Number(inner) if let Some(integer) = as_exact_integer(inner) => Ok(integer.into()),
+        other => Err(invalid(format!("expected an integer, found {:?}", other))),
+    }
+}
+
+native_operator! {
+    /// The address at an offset from a base address or CIDR, as in `(( ips "10.0.0.0/24" 5 ))`,
+    /// or that many consecutive addresses from there, as in `(( ips &range 10 3 ))`.
+    /// Addresses must lie in the CIDR, when given one.
+    IpsOperator, "ips", Middle, {
+        base: positional(StringLiteral | Reference | Expr | Fallback),
+        offset: positional(NumberLiteral | Reference | Expr),
+        count: optional(NumberLiteral | Reference | Expr),
+    }
+}
+
+impl OperatorPayload for IpsOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let base = match self.base.evaluate(context)? {
+            OperatorData::String(inner) => Base::parse(&inner)?,
+            other => return Err(invalid(format!("expected an IP address or a CIDR, found {:?}", other))),
+        };
+        let first = base.offset(evaluate_integer(&self.offset, context)?)?;
+
+        let count = match &self.count {
+            Some(count) => evaluate_integer(count, context)?,
+            None => return Ok(OperatorData::String(base.format(first))),
+        };
+        if count < 0 {
+            return Err(invalid(format!("cannot take {} addresses", count)))
+        }
+
+        let mut addresses = Vec::with_capacity(count as usize);
+        for index in 0..count as u128 {
+            let address = first.checked_add(index)
+                .filter(|address| *address <= base.max() && base.network.is_none_or(|(_, last)| *address <= last))
+                .ok_or_else(|| invalid(format!("{} addresses from {} go out of range", count, base.format(first))))?;
+            addresses.push(OperatorData::String(base.format(address)));
+        }
+        Ok(OperatorData::Sequence(addresses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_offsets() {
+        let v4 = Base::parse("10.0.0.0/24").unwrap();
+        assert_eq!(v4.format(v4.offset(5).unwrap()), "10.0.0.5");
+        assert_eq!(v4.format(v4.offset(-1).unwrap()), "10.0.0.255");
+        assert!(v4.offset(256).is_err() && v4.offset(-257).is_err());
+
+        let unbounded = Base::parse("10.0.0.250").unwrap();
+        assert_eq!(unbounded.format(unbounded.offset(10).unwrap()), "10.0.1.4");
+        assert!(unbounded.offset(-1).is_err());
+        assert!(Base::parse("255.255.255.255").unwrap().offset(1).is_err());
+
+        let v6 = Base::parse("2001:db8::/126").unwrap();
+        assert_eq!(v6.format(v6.offset(3).unwrap()), "2001:db8::3");
+        assert!(v6.offset(4).is_err());
+        assert_eq!(Base::parse("::/0").unwrap().offset(-1).unwrap(), u128::MAX);
+
+        assert!(Base::parse("10.0.0.0/33").is_err());
+        assert!(Base::parse("not an ip").is_err());
+    }
+
+    #[test]
+    fn test_ips() {
+        let mut data = document("
+networks:
+  internal: {range: 10.0.0.0/28}
+gateway: (( ips \"10.0.0.0/24\" 1 ))
+static: (( ips &networks.internal.range 10 3 ))
+v6: (( ips \"2001:db8::/64\" -1 ))
+overflow: (( ips &networks.internal.range 14 3 ))
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, path(&["overflow"]));
+        assert_eq!(string_at(&data, &["gateway"]), "10.0.0.1");
+        assert_eq!(string_at(&data, &["v6"]), "2001:db8::ffff:ffff:ffff:ffff");
+        assert_eq!(yaml_at(&data, &["static"]), "- 10.0.0.10\n- 10.0.0.11\n- 10.0.0.12\n");
+    }
+}
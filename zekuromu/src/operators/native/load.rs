@@ -0,0 +1,39 @@
+use crate::{data::{OperatorData, operators::Expr}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::Signature}, raw};
+
+use super::{NativeOperatorDefinition, file::{FileLocation, path_signature, try_parsing_path_operator}};
+
+/// Splices a YAML or JSON file in, as in `(( load "shared/networks.yml" ))`.
+/// Operators in the loaded file are kept as strings, and not executed.
+#[derive(Debug, Clone)]
+pub struct LoadOperator {
+    path: OperatorArgument,
+    location: FileLocation,
+}
+
+impl OperatorPriority for LoadOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::Middle
+    }
+}
+
+impl NativeOperatorDefinition for LoadOperator {
+    const NAME: &'static str = "load";
+
+    fn signature() -> Signature {
+        path_signature()
+    }
+
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        let (path, location) = try_parsing_path_operator(Self::NAME, expr, registry)?;
+        Ok(LoadOperator { path, location })
+    }
+}
+
+impl OperatorPayload for LoadOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let file = self.location.resolve(&self.path, context)?;
+        raw::parse_file_as_raw_data(&file)
+            .map(OperatorData::from)
+            .map_err(|reason| OperatorExecutionErrorReason::FileUnavailable { path: file, reason })
+    }
+}
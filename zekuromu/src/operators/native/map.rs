@@ -0,0 +1,194 @@
+use crate::{data::{DataKey, OperatorData, ReferenceError, operators::{Argument, Expr, Reference}}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::{ArgumentKind, Signature}}};
+
+use super::NativeOperatorDefinition;
+
+/// Bound to each element in turn.
+pub const ITEM_VARIABLE: &str = "item";
+/// Bound to the index or key of each element in turn.
+pub const KEY_VARIABLE: &str = "key";
+
+/// Instantiates a template once per element of a sequence or mapping, as in `(( map &names &templates.service ))`,
+/// giving a sequence, or a mapping with the same keys.
+/// Operators in the template are executed where the instance ends up, relative references pointing within it,
+/// `&$item` being bound to the element and `&$key` to its index or key.
+/// The template itself is never executed, and is kept in the document as written.
+#[derive(Debug, Clone)]
+pub struct MapOperator {
+    collection: OperatorArgument,
+    template: Reference,
+}
+
+impl OperatorPriority for MapOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::Middle
+    }
+}
+
+impl NativeOperatorDefinition for MapOperator {
+    const NAME: &'static str = "map";
+    const TEMPLATES: &'static [usize] = &[1];
+
+    fn signature() -> Signature {
+        Signature::new()
+            .positional(&[ArgumentKind::Reference, ArgumentKind::Expr, ArgumentKind::Fallback])
+            .positional(&[ArgumentKind::Reference])
+    }
+
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        if expr.name.0 != Self::NAME {
+            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
+        }
+
+        MapOperator::signature().check(&expr.arguments)?;
+
+        match &expr.arguments[1] {
+            Argument::Reference(template) => Ok(MapOperator {
+                collection: OperatorArgument::try_from_argument(&expr.arguments[0], 0, registry)?,
+                template: template.clone(),
+            }),
+            _ => unreachable!("argument kinds are checked by the signature"),
+        }
+    }
+}
+
+impl OperatorPayload for MapOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let (template_path, template) = context.root.locate(&self.template, &context.path)
+            .map_err(OperatorExecutionErrorReason::ReferenceUnavailable)?;
+        if context.path.starts_with(&template_path) {
+            return Err(OperatorExecutionErrorReason::OtherError("cannot lie in its own template".to_owned()))
+        }
+
+        // Each instance, where it will lie, along with the index or key of its element, and the element.
+        let (skeleton, elements): (_, Vec<_>) = match self.collection.evaluate(context)? {
+            OperatorData::Sequence(inner) => (
+                OperatorData::Sequence(vec![template.clone(); inner.len()]),
+                inner.into_iter().enumerate()
+                    .map(|(index, item)| (DataKey::from(index.to_string()), OperatorData::Number(index as f64), item))
+                    .collect(),
+            ),
+            OperatorData::Mapping(inner) => (
+                OperatorData::Mapping(inner.keys().map(|key| (key.clone(), template.clone())).collect()),
+                inner.into_iter()
+                    .map(|(key, item)| (key.clone(), OperatorData::String(key.as_str().to_owned()), item))
+                    .collect(),
+            ),
+            other => return Err(OperatorExecutionErrorReason::OtherError(format!("expected a sequence or a mapping, found {:?}", other))),
+        };
+
+        // Instances are built in place of this operator, in a copy of the document,
+        // so that their operators run in order and can reference each other, as anywhere else.
+        let mut document = context.root.clone();
+        *document.get_mut(&context.path)
+            .ok_or_else(|| OperatorExecutionErrorReason::OtherError(format!("cannot instantiate at '{}', which does not exist", context.path)))? = skeleton;
+
+        let mut operators = vec![];
+        for (key, index_or_key, item) in elements {
+            let bindings = context.bindings.with(KEY_VARIABLE, index_or_key).with(ITEM_VARIABLE, item);
+            let mut found = vec![];
+            template.collect_operators(context.path.child(key), &[], &mut found);
+            operators.extend(found.into_iter().map(|(path, operator)| (path, operator, bindings.clone())));
+        }
+
+        let errors = document.execute_collected(operators);
+        // Waiting on the rest of the document comes first, as this operator is then retried.
+        let first = errors.iter()
+            .position(|error| matches!(error.reason, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::Unevaluated(_))))
+            .unwrap_or_default();
+        if let Some(error) = errors.into_iter().nth(first) {
+            return Err(error.reason)
+        }
+
+        Ok(document.get_mut(&context.path).map(std::mem::take).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::ReferenceError, test_helpers::*};
+
+    #[test]
+    fn test_map() {
+        let mut data = document(r#"
+names: [worker, api]
+ports: {http: 80, metrics: 9090}
+defaults: {replicas: 2}
+templates:
+  service:
+    name: (( upper &$item ))
+    index: (( grab &$key ))
+    replicas: (( grab &defaults.replicas ))
+    exposed: (( ternary (eq &$item "api") &$item.missing.key "no" ))
+    kind: service
+  port: {name: (( grab &$key )), port: (( grab &$item ))}
+  typo: (( grab &$itme ))
+tag: (( lower &$item ))
+services: (( map &names &templates.service ))
+listeners: (( map &ports &templates.port ))
+sorted: (( sort (map &names &tag) ))
+missing: (( map &names &nowhere ))
+typo: (( map &names &templates.typo ))
+stray: (( grab &$item ))
+"#);
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        let failed: Vec<_> = errors.iter().map(|(at, _)| at.clone()).collect();
+        assert_eq!(failed, vec![path(&["missing"]), path(&["services"]), path(&["stray"]), path(&["typo"])]);
+        for (_, reason) in &errors[2..] {
+            assert!(matches!(reason, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::UnboundVariable(name)) if name == "item" || name == "itme"), "{:?}", reason);
+        }
+
+        // Only the chosen branch is evaluated, which fails for `api` alone.
+        assert!(matches!(&errors[1].1, OperatorExecutionErrorReason::ReferenceUnavailable(ReferenceError::NotTraversable(_))), "{:?}", errors[1].1);
+
+        assert_eq!(number_at(&data, &["listeners", "metrics", "port"]), 9090.0);
+        assert_eq!(string_at(&data, &["listeners", "http", "name"]), "http");
+        assert_eq!(yaml_at(&data, &["sorted"]), "- api\n- worker\n");
+
+        // Templates are kept as written.
+        assert_eq!(string_at(&data, &["tag"]), "(( lower &$item ))");
+        assert_eq!(string_at(&data, &["templates", "typo"]), "(( grab &$itme ))");
+    }
+
+    #[test]
+    fn test_map_keeps_templates() {
+        let mut data = document(r#"
+names: [worker, api]
+defaults: {replicas: 2}
+templates:
+  service:
+    name: (( upper &$item ))
+    index: (( grab &$key ))
+    base: (( inject &defaults ))
+    kind: service
+    replicas: 1
+services: (( map &names &templates.service ))
+"#);
+        data.execute_operators().unwrap();
+
+        // `inject` merges into each instance, its own keys winning.
+        assert_eq!(yaml_at(&data, &[]), "defaults:\n  replicas: 2\nnames:\n- worker\n- api\nservices:\n- index: 0\n  kind: service\n  name: WORKER\n  replicas: 1\n- index: 1\n  kind: service\n  name: API\n  replicas: 1\ntemplates:\n  service:\n    base: (( inject &defaults ))\n    index: (( grab &$key ))\n    kind: service\n    name: (( upper &$item ))\n    replicas: 1\n");
+    }
+
+    #[test]
+    fn test_map_relative_references() {
+        let mut data = document(r#"
+names: [worker, api]
+templates:
+  host:
+    alias: (( upper &.label ))
+    label: (( lower &.name ))
+    name: (( replace &$item "e" "3" ))
+hosts: (( map &names &templates.host ))
+"#);
+        data.execute_operators().unwrap();
+
+        // Fields reference each other within their own instance, whatever order they are executed in.
+        assert_eq!(string_at(&data, &["hosts", "0", "name"]), "work3r");
+        assert_eq!(string_at(&data, &["hosts", "0", "label"]), "work3r");
+        assert_eq!(string_at(&data, &["hosts", "0", "alias"]), "WORK3R");
+        assert_eq!(string_at(&data, &["hosts", "1", "alias"]), "API");
+        assert_eq!(string_at(&data, &["templates", "host", "label"]), "(( lower &.name ))");
+    }
+}
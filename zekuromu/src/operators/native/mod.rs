@@ -1,60 +1,381 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc, sync::LazyLock};
 
-use crate::{data::operators::Expr, operators::{Operator, OperatorParsingError, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorSource, native::{expect::ExpectOperator, grab::GrabOperator, param::ParamOperator}}};
+use crate::{data::operators::{Argument, Expr, Keyword, Reference}, operators::{Operator, OperatorParsingError, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorSource, registry::OperatorRegistry, signature::Signature}};
 
+/// Declares a native operator from its name, priority, and arguments, in order.
+/// Each argument is a field written as `name: mode(Kind | ...)`, where `mode` is a method of `Signature`.
+/// The field holds the literal itself when a single kind is allowed, the parsed `Operator` for a single `Expr`,
+/// and an `OperatorArgument` otherwise, wrapped in an `Option` when optional, and a `Vec` when variadic.
+/// A single `Keyword` can be narrowed to some keywords, as in `positional(Keyword) in [Yaml, Json]`.
+/// Fields taken from the registry follow in `from registry { field: Type = getter }`, `getter` taking the registry.
+/// Generates the struct, its parsing from an `Expr`, its priority and its `NativeOperatorDefinition`;
+/// only `OperatorPayload` is left to implement.
+macro_rules! native_operator {
+    (
+        $(#[$meta:meta])*
+        $operator:ident, $name:literal, $priority:ident, { $($field:ident: $mode:ident($($kind:ident)|+) $(in [$($keyword:ident),+])?),* $(,)? }
+        $(, from registry { $($(#[$extra_meta:meta])* $extra:ident: $extra_type:ty = $getter:path),* $(,)? })?
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $operator {
+            $($field: native_field_type!($mode, native_argument_type!($($kind)|+)),)*
+            $($($(#[$extra_meta])* $extra: $extra_type,)*)?
+        }
+
+        impl $crate::operators::OperatorPriority for $operator {
+            fn priority(&self) -> $crate::operators::OperatorPriorityRank {
+                $crate::operators::OperatorPriorityRank::$priority
+            }
+        }
+
+        impl $crate::operators::native::NativeOperatorDefinition for $operator {
+            const NAME: &'static str = $name;
+
+            fn signature() -> $crate::operators::signature::Signature {
+                $crate::operators::signature::Signature::new()
+                    $(.$mode(&[$($crate::operators::signature::ArgumentKind::$kind),+]))*
+            }
+
+            #[allow(unused_variables)]
+            fn try_from_expr(
+                value: &$crate::data::operators::Expr,
+                registry: &$crate::operators::registry::OperatorRegistry,
+            ) -> Result<Self, $crate::operators::OperatorParsingErrorReason> {
+                if value.name.0 != $name {
+                    return Err($crate::operators::OperatorParsingErrorReason::NameDoesNotMatch)
+                }
+
+                <$operator as $crate::operators::native::NativeOperatorDefinition>::signature().check(&value.arguments)?;
+
+                #[allow(unused_mut)]
+                let mut arguments = value.arguments.iter().enumerate();
+                Ok($operator {
+                    $($field: native_field_value!($mode, arguments, registry, [$($($keyword),+)?], $($kind)|+),)*
+                    $($($extra: $getter(registry),)*)?
+                })
+            }
+        }
+    };
+}
+
+macro_rules! native_argument_type {
+    (Expr) => { $crate::operators::Operator };
+    (Fallback) => { $crate::operators::OperatorArgument };
+    ($kind:ident) => { $crate::data::operators::$kind };
+    ($($kind:ident)|+) => { $crate::operators::OperatorArgument };
+}
+
+macro_rules! native_field_type {
+    (positional, $type:ty) => { $type };
+    (optional, $type:ty) => { Option<$type> };
+    (variadic, $type:ty) => { Vec<$type> };
+}
+
+// Arguments were checked against the signature beforehand, only nested calls and narrowed keywords can still fail.
+macro_rules! native_argument_value {
+    (($position:expr, $argument:expr), $registry:ident, [$($keyword:ident),+], Keyword) => {
+        match $argument {
+            $crate::data::operators::Argument::Keyword(inner) => {
+                $crate::operators::native::check_keyword(*inner, $position, &[$($crate::data::operators::Keyword::$keyword),+])
+            },
+            _ => unreachable!("argument kinds are checked by the signature"),
+        }
+    };
+    (($position:expr, $argument:expr), $registry:ident, [], Expr) => {
+        match $argument {
+            $crate::data::operators::Argument::Expr(inner) => $registry.try_parsing_nested_operator(inner, $position),
+            _ => unreachable!("argument kinds are checked by the signature"),
+        }
+    };
+    (($position:expr, $argument:expr), $registry:ident, [], Fallback) => {
+        $crate::operators::OperatorArgument::try_from_argument($argument, $position, $registry)
+    };
+    (($position:expr, $argument:expr), $registry:ident, [], $kind:ident) => {
+        match $argument {
+            $crate::data::operators::Argument::$kind(inner) => Ok(inner.clone()),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("argument kinds are checked by the signature"),
+        }
+    };
+    (($position:expr, $argument:expr), $registry:ident, [], $($kind:ident)|+) => {
+        $crate::operators::OperatorArgument::try_from_argument($argument, $position, $registry)
+    };
+}
+
+macro_rules! native_field_value {
+    (positional, $arguments:ident, $registry:ident, $keywords:tt, $($kind:ident)|+) => {{
+        let (position, argument) = $arguments.next().expect("argument count is checked by the signature");
+        native_argument_value!((position, argument), $registry, $keywords, $($kind)|+)?
+    }};
+    (optional, $arguments:ident, $registry:ident, $keywords:tt, $($kind:ident)|+) => {
+        $arguments.next()
+            .map(|(position, argument)| native_argument_value!((position, argument), $registry, $keywords, $($kind)|+))
+            .transpose()?
+    };
+    (variadic, $arguments:ident, $registry:ident, $keywords:tt, $($kind:ident)|+) => {
+        $arguments.by_ref()
+            .map(|(position, argument)| native_argument_value!((position, argument), $registry, $keywords, $($kind)|+))
+            .collect::<Result<_, _>>()?
+    };
+}
+
+/// Declares every native operator, along with its variant in `NativeOperator`.
+macro_rules! native_operators {
+    ($($variant:ident => $operator:ty),* $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum NativeOperator {
+            $($variant),*
+        }
+
+        impl NativeOperator {
+            pub const ALL: &'static [NativeOperator] = &[$(NativeOperator::$variant),*];
+
+            pub fn from_name(name: &str) -> Option<NativeOperator> {
+                BY_NAME.get(name).copied()
+            }
+
+            /// Nested calls are parsed through `registry`, and can be registered operators.
+            pub fn try_parsing_operator(expr: &Expr, registry: &OperatorRegistry) -> Result<Operator, OperatorParsingError> {
+                let native = NativeOperator::from_name(&expr.name.0)
+                    .ok_or((None, OperatorParsingErrorReason::NoneMatched))?;
+
+                if registry.is_disabled(native) {
+                    return Err((Some(OperatorSource::Native(native)), OperatorParsingErrorReason::Disabled))
+                }
+
+                native.parse(expr, registry)
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(NativeOperator::$variant => <$operator as NativeOperatorDefinition>::NAME),*
+                }
+            }
+
+            pub fn signature(&self) -> Signature {
+                match self {
+                    $(NativeOperator::$variant => <$operator as NativeOperatorDefinition>::signature()),*
+                }
+            }
+
+            pub fn templates(&self) -> &'static [usize] {
+                match self {
+                    $(NativeOperator::$variant => <$operator as NativeOperatorDefinition>::TEMPLATES),*
+                }
+            }
+
+            fn parse(&self, expr: &Expr, registry: &OperatorRegistry) -> Result<Operator, OperatorParsingError> {
+                match self {
+                    $(NativeOperator::$variant => parse_native::<$operator>(expr, *self, registry)),*
+                }
+            }
+        }
+    };
+}
+
+pub mod base64;
+pub mod collections;
+pub mod conditions;
+pub mod defer;
+pub mod env;
 pub mod expect;
+pub mod file;
 pub mod grab;
+pub mod hash;
+pub mod inject;
+pub mod ips;
+pub mod load;
+pub mod map;
 pub mod param;
+pub mod serialization;
+pub mod strings;
+pub mod values;
+
+/// Implemented through `native_operator!`.
+pub trait NativeOperatorDefinition: OperatorPayload + OperatorPriority + Sized + 'static {
+    const NAME: &'static str;
+
+    /// Positions of the arguments referencing templates, which are left out when executing the document.
+    const TEMPLATES: &'static [usize] = &[];
 
-#[derive(Debug, Clone)]
-pub enum NativeOperator {
-    Expect,
-    Grab,
-    Param,
+    fn signature() -> Signature;
+
+    /// Nested calls are parsed through `registry`.
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason>;
 }
 
-fn try_parse_native<'a, T>(expr: &'a Expr, kind: NativeOperator) -> Option<Result<Operator, OperatorParsingError>>
-where T: TryFrom<&'a Expr, Error = OperatorParsingErrorReason> + OperatorPayload + OperatorPriority + 'static
-{
-    let maybe_op: Result<Rc<T>, OperatorParsingErrorReason> = expr.try_into().map(|op| Rc::new(op));
+native_operators! {
+    And => conditions::AndOperator,
+    Base64 => base64::Base64Operator,
+    Base64Decode => base64::Base64DecodeOperator,
+    CartesianProduct => collections::CartesianProductOperator,
+    Defer => defer::DeferOperator,
+    Empty => values::EmptyOperator,
+    Env => env::EnvOperator,
+    Eq => conditions::EqOperator,
+    Expect => expect::ExpectOperator,
+    File => file::FileOperator,
+    Filter => collections::FilterOperator,
+    Flatten => collections::FlattenOperator,
+    Grab => grab::GrabOperator,
+    Gt => conditions::GtOperator,
+    In => conditions::InOperator,
+    Inject => inject::InjectOperator,
+    Ips => ips::IpsOperator,
+    Keys => collections::KeysOperator,
+    Load => load::LoadOperator,
+    Lower => strings::LowerOperator,
+    Lt => conditions::LtOperator,
+    Map => map::MapOperator,
+    Ne => conditions::NeOperator,
+    Negate => values::NegateOperator,
+    Not => conditions::NotOperator,
+    Null => values::NullOperator,
+    Or => conditions::OrOperator,
+    Param => param::ParamOperator,
+    Parse => serialization::ParseOperator,
+    RegexReplace => strings::RegexReplaceOperator,
+    Replace => strings::ReplaceOperator,
+    Sha1 => hash::Sha1Operator,
+    Sha256 => hash::Sha256Operator,
+    Sort => collections::SortOperator,
+    Split => strings::SplitOperator,
+    Stringify => serialization::StringifyOperator,
+    Substr => strings::SubstrOperator,
+    Ternary => conditions::TernaryOperator,
+    Trim => strings::TrimOperator,
+    Unique => collections::UniqueOperator,
+    Upper => strings::UpperOperator,
+    Values => collections::ValuesOperator,
+}
 
-    match maybe_op {
-        Ok(op) => {
-            let priority = op.priority();
-            Some(Ok( Operator {
-            source: OperatorSource::Native(kind),
-            payload: op,
-            priority: priority,
-        }))
-        },
-        Err(error) => {
-            match error {
-                OperatorParsingErrorReason::NameDoesNotMatch => None,
-                _ => Some(Err((
-                        Some(kind),
-                        error
-                )))
-            }
+static BY_NAME: LazyLock<HashMap<&'static str, NativeOperator>> = LazyLock::new(|| {
+    NativeOperator::ALL.iter()
+        .map(|native| (native.name(), *native))
+        .collect()
+});
+
+fn parse_native<T: NativeOperatorDefinition>(expr: &Expr, kind: NativeOperator, registry: &OperatorRegistry) -> Result<Operator, OperatorParsingError> {
+    let op = T::try_from_expr(expr, registry)
+        .map_err(|error| (Some(OperatorSource::Native(kind)), error))?;
+    let priority = op.priority();
+
+    Ok(Operator {
+        source: OperatorSource::Native(kind),
+        payload: Rc::new(op),
+        priority,
+        expr: Rc::new(expr.clone()),
+    })
+}
+
+/// References found at the template positions of native calls, in `expr` or nested in it.
+pub(crate) fn template_references(expr: &Expr) -> Vec<Reference> {
+    fn collect(argument: &Argument, references: &mut Vec<Reference>) {
+        match argument {
+            Argument::Expr(inner) => references.extend(template_references(inner)),
+            Argument::Fallback(alternatives) => alternatives.iter().for_each(|alternative| collect(alternative, references)),
+            _ => {},
         }
     }
+
+    let positions = NativeOperator::from_name(&expr.name.0).map_or(&[][..], |native| native.templates());
+    let mut references: Vec<Reference> = positions.iter()
+        .filter_map(|position| match expr.arguments.get(*position) {
+            Some(Argument::Reference(inner)) => Some(inner.clone()),
+            _ => None,
+        })
+        .collect();
+    for argument in &expr.arguments {
+        collect(argument, &mut references);
+    }
+    references
 }
 
-// Better but still should be a macro at some point
-impl NativeOperator {
-    pub fn try_parsing_operator(expr: &Expr) -> Result<Operator, OperatorParsingError> {
-        if let Some(operator) = try_parse_native::<ExpectOperator>(expr, NativeOperator::Expect) {
-            return operator;
+/// Keeps `keyword` if it is `allowed`, `position` starting at 0.
+pub(crate) fn check_keyword(keyword: Keyword, position: usize, allowed: &[Keyword]) -> Result<Keyword, OperatorParsingErrorReason> {
+    if allowed.contains(&keyword) {
+        return Ok(keyword)
+    }
+
+    let names: Vec<&str> = allowed.iter().map(Keyword::as_str).collect();
+    let expected = match names.split_last() {
+        Some((last, others)) if !others.is_empty() => format!("{} or {}", others.join(", "), last),
+        _ => names.concat(),
+    };
+    Err(OperatorParsingErrorReason::InvalidArgument { position, reason: format!("expected {}, found {}", expected, keyword.as_str()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data::operators::{Argument, ExprLocation, OperatorName, StringLiteral}, operators::{OperatorArgument, signature::ArgumentKind}};
+
+    native_operator! {
+        SignatureOperator, "signature", Middle, {
+            first: positional(Reference),
+            second: optional(StringLiteral | NumberLiteral),
+            rest: variadic(StringLiteral),
         }
+    }
 
-        if let Some(operator) = try_parse_native::<ParamOperator>(expr, NativeOperator::Param) {
-            return operator;
+    impl OperatorPayload for SignatureOperator {
+        fn execute(&self, _context: &crate::operators::EvaluationContext) -> crate::operators::OperatorExecutionResult {
+            Err(crate::operators::OperatorExecutionErrorReason::Unimplemented)
         }
+    }
+
+    fn parse(expr: Expr) -> Result<Operator, OperatorParsingError> {
+        NativeOperator::try_parsing_operator(&expr, &OperatorRegistry::new())
+    }
 
-        if let Some(operator) = try_parse_native::<GrabOperator>(expr, NativeOperator::Grab) {
-            return operator;
+    fn expr(name: &str, arguments: Vec<Argument>) -> Expr {
+        Expr { name: OperatorName(name.to_owned()), arguments, location: ExprLocation::default() }
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for native in NativeOperator::ALL {
+            assert_eq!(NativeOperator::from_name(native.name()), Some(*native));
         }
+        assert_eq!(NativeOperator::from_name("unknown"), None);
+    }
 
-        Err((None, OperatorParsingErrorReason::NoneMatched))
+    #[test]
+    fn test_dispatch() {
+        let operator = parse(expr("expect", vec![Argument::StringLiteral(StringLiteral("message".to_owned()))])).unwrap();
+        assert!(matches!(operator.source(), OperatorSource::Native(NativeOperator::Expect)));
+        assert_eq!(operator.priority(), crate::operators::OperatorPriorityRank::Last);
+
+        assert!(matches!(
+            parse(expr("param", vec![Argument::StringLiteral(StringLiteral("extra".to_owned()))])),
+            Err((Some(OperatorSource::Native(NativeOperator::Param)), OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum: 0, maximum: Some(0), found: 1 }))
+        ));
+        assert!(matches!(
+            parse(expr("grab", vec![Argument::StringLiteral(StringLiteral("a".to_owned()))])),
+            Err((Some(OperatorSource::Native(NativeOperator::Grab)), OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position: 0, found: ArgumentKind::StringLiteral, .. }))
+        ));
+        assert!(matches!(
+            parse(expr("unknown", vec![])),
+            Err((None, OperatorParsingErrorReason::NoneMatched))
+        ));
     }
-}
 
+    #[test]
+    fn test_macro_signature() {
+        let reference = Expr::try_parse("(( signature &a ))").unwrap();
+        let operator = SignatureOperator::try_from_expr(&reference, &OperatorRegistry::new()).unwrap();
+        assert!(operator.second.is_none() && operator.rest.is_empty());
+
+        let full = Expr::try_parse("(( signature &a 12 \"b\" 'c' ))").unwrap();
+        let operator = SignatureOperator::try_from_expr(&full, &OperatorRegistry::new()).unwrap();
+        assert!(matches!(&reference.arguments[0], Argument::Reference(inner) if *inner == operator.first));
+        assert!(matches!(operator.second, Some(OperatorArgument::NumberLiteral(_))));
+        assert_eq!(operator.rest, vec![StringLiteral("b".to_owned()), StringLiteral("c".to_owned())]);
+
+        assert!(matches!(
+            SignatureOperator::try_from_expr(&Expr::try_parse("(( signature &a 12 3 ))").unwrap(), &OperatorRegistry::new()),
+            Err(OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position: 2, found: ArgumentKind::NumberLiteral, .. })
+        ));
+    }
+}
@@ -1,34 +1,13 @@
-use crate::{data::{DataKeyPath, OperatorData, operators::{Argument, Expr, StringLiteral}}, operators::{OperatorExecutionErrorReason, OperatorExecutionErrorResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank}};
+use crate::operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload};
 
-#[derive(Debug, Clone)]
-pub struct ParamOperator {}
-
-impl TryFrom<&Expr> for ParamOperator {
-    type Error = OperatorParsingErrorReason;
-
-    fn try_from(value: &Expr) -> Result<Self, Self::Error> {
-        if value.name.0 != "param" {
-            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
-        }
-
-        if value.arguments.len() != 0 {
-            return Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch)
-        }
-
-        Ok(ParamOperator {})
-    }
+native_operator! {
+    ParamOperator, "param", AfterFirst, {}
 }
 
 impl OperatorPayload for ParamOperator {
-    fn execute(&self, _data: &mut OperatorData, path: &DataKeyPath) -> OperatorExecutionErrorResult {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
         Err(OperatorExecutionErrorReason::OtherError(
-            format!("At path '{}', expected a parameter to be overriden", path)
+            format!("At path '{}', expected a parameter to be overriden", context.path)
         ))
     }
 }
-
-impl OperatorPriority for ParamOperator {
-    fn priority(&self) -> OperatorPriorityRank {
-        OperatorPriorityRank::AfterFirst
-    }
-}
@@ -0,0 +1,70 @@
+use crate::{data::{OperatorData, RawData, operators::Keyword}, operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}, raw};
+
+native_operator! {
+    /// Writes a value as a YAML or JSON string, as in `(( stringify json &subtree ))`, like the output is written.
+    StringifyOperator, "stringify", Middle, {
+        format: positional(Keyword) in [Yaml, Json],
+        value: positional(StringLiteral | NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for StringifyOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = RawData::try_from(self.value.evaluate(context)?)
+            .map_err(|_| OperatorExecutionErrorReason::OtherError("value still holds operators".to_owned()))?;
+        let written = match self.format {
+            Keyword::Yaml => raw::serialize_raw_data_as_yaml(&value),
+            Keyword::Json => raw::serialize_raw_data_as_json(&value),
+            _ => unreachable!("keywords are checked when parsing"),
+        };
+        written
+            .map(OperatorData::String)
+            .map_err(OperatorExecutionErrorReason::OtherError)
+    }
+}
+
+native_operator! {
+    /// Reads a YAML or JSON string, as files are read. Operators it holds are kept as strings.
+    ParseOperator, "parse", Middle, { text: positional(StringLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for ParseOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match self.text.evaluate(context)? {
+            OperatorData::String(inner) => raw::parse_str_as_raw_data(&inner)
+                .map(OperatorData::from)
+                .map_err(|error| OperatorExecutionErrorReason::OtherError(format!("cannot parse, {}", error))),
+            other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a string to parse, found {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_stringify_and_parse() {
+        let mut data = document("
+config: {port: 8080, name: api, ratio: 0.5}
+as_json: (( stringify json &config ))
+as_yaml: (( stringify yaml &config.port ))
+blob: '{\"b\": [1, 2], \"a\": \"(( grab &x ))\"}'
+parsed: (( parse &blob ))
+roundtrip: (( parse (stringify yaml &config) ))
+invalid: '(( parse \"[1, \" ))'
+");
+        let errors = reasons(data.execute_operators().unwrap_err());
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, path(&["invalid"]));
+        assert_eq!(string_at(&data, &["as_json"]), r#"{"name":"api","port":8080,"ratio":0.5}"#);
+        assert_eq!(string_at(&data, &["as_yaml"]), "8080\n");
+        assert_eq!(number_at(&data, &["parsed", "b", "1"]), 2.0);
+        // Operators in parsed blobs are not evaluated.
+        assert_eq!(string_at(&data, &["parsed", "a"]), "(( grab &x ))");
+        assert!(data.get(&path(&["roundtrip"])).unwrap().deep_equals(data.get(&path(&["config"])).unwrap()));
+
+        assert!(parsing_error("a: (( stringify hash &b ))").starts_with("at 'a': stringify: argument 1 expected yaml or json, found hash"));
+    }
+}
@@ -0,0 +1,205 @@
+use regex::Regex;
+
+use crate::{data::{OperatorData, operators::{Argument, Expr, as_exact_integer}}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorParsingErrorReason, OperatorPayload, OperatorPriority, OperatorPriorityRank, registry::OperatorRegistry, signature::{ArgumentKind, Signature}}};
+
+use super::NativeOperatorDefinition;
+
+fn evaluate_string(argument: &OperatorArgument, context: &EvaluationContext) -> Result<String, OperatorExecutionErrorReason> {
+    match argument.evaluate(context)? {
+        OperatorData::String(inner) => Ok(inner),
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a string, found {:?}", other))),
+    }
+}
+
+fn evaluate_index(argument: &OperatorArgument, context: &EvaluationContext) -> Result<usize, OperatorExecutionErrorReason> {
+    match argument.evaluate(context)? {
+        OperatorData::Number(inner) if let Some(index) = as_exact_integer(inner).and_then(|integer| usize::try_from(integer).ok()) => Ok(index),
+        other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a non-negative integer, found {:?}", other))),
+    }
+}
+
+native_operator! {
+    /// Replaces every occurrence of a substring, as in `(( replace &name "-" "_" ))`.
+    ReplaceOperator, "replace", Middle, {
+        value: positional(StringLiteral | Reference | Expr | Fallback),
+        from: positional(StringLiteral | Reference | Expr | Fallback),
+        to: positional(StringLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for ReplaceOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = evaluate_string(&self.value, context)?;
+        let from = evaluate_string(&self.from, context)?;
+        if from.is_empty() {
+            return Err(OperatorExecutionErrorReason::OtherError("cannot replace an empty string".to_owned()))
+        }
+        Ok(OperatorData::String(value.replace(&from, &evaluate_string(&self.to, context)?)))
+    }
+}
+
+/// Replaces every match of a regex, as in `(( regex-replace &x "^v(\d+)" "$1" ))`, groups being written `$1` or `${name}`.
+/// Escapes unknown to strings, such as `\d`, are kept as is in patterns.
+/// The pattern must be a literal, and is compiled once when parsing.
+#[derive(Debug, Clone)]
+pub struct RegexReplaceOperator {
+    value: OperatorArgument,
+    pattern: Regex,
+    replacement: OperatorArgument,
+}
+
+impl OperatorPriority for RegexReplaceOperator {
+    fn priority(&self) -> OperatorPriorityRank {
+        OperatorPriorityRank::Middle
+    }
+}
+
+impl NativeOperatorDefinition for RegexReplaceOperator {
+    const NAME: &'static str = "regex-replace";
+
+    fn signature() -> Signature {
+        let string = &[ArgumentKind::StringLiteral, ArgumentKind::Reference, ArgumentKind::Expr, ArgumentKind::Fallback];
+        Signature::new()
+            .positional(string)
+            .positional(&[ArgumentKind::StringLiteral])
+            .positional(string)
+    }
+
+    fn try_from_expr(expr: &Expr, registry: &OperatorRegistry) -> Result<Self, OperatorParsingErrorReason> {
+        if expr.name.0 != Self::NAME {
+            return Err(OperatorParsingErrorReason::NameDoesNotMatch)
+        }
+
+        RegexReplaceOperator::signature().check(&expr.arguments)?;
+
+        let pattern = match &expr.arguments[1] {
+            Argument::StringLiteral(inner) => Regex::new(&inner.0)
+                .map_err(|error| OperatorParsingErrorReason::InvalidArgument { position: 1, reason: format!("is not a valid regex, {}", error) })?,
+            _ => unreachable!("argument kinds are checked by the signature"),
+        };
+
+        Ok(RegexReplaceOperator {
+            value: OperatorArgument::try_from_argument(&expr.arguments[0], 0, registry)?,
+            pattern,
+            replacement: OperatorArgument::try_from_argument(&expr.arguments[2], 2, registry)?,
+        })
+    }
+}
+
+impl OperatorPayload for RegexReplaceOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = evaluate_string(&self.value, context)?;
+        let replacement = evaluate_string(&self.replacement, context)?;
+        Ok(OperatorData::String(self.pattern.replace_all(&value, replacement.as_str()).into_owned()))
+    }
+}
+
+native_operator! {
+    /// Splits a string on every occurrence of a separator, into a sequence of strings.
+    SplitOperator, "split", Middle, {
+        value: positional(StringLiteral | Reference | Expr | Fallback),
+        separator: positional(StringLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for SplitOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = evaluate_string(&self.value, context)?;
+        let separator = evaluate_string(&self.separator, context)?;
+        if separator.is_empty() {
+            return Err(OperatorExecutionErrorReason::OtherError("cannot split on an empty separator".to_owned()))
+        }
+        Ok(OperatorData::Sequence(value.split(&separator).map(|part| OperatorData::String(part.to_owned())).collect()))
+    }
+}
+
+native_operator! {
+    /// Uppercases a string.
+    UpperOperator, "upper", Middle, { value: positional(StringLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for UpperOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::String(evaluate_string(&self.value, context)?.to_uppercase()))
+    }
+}
+
+native_operator! {
+    /// Lowercases a string.
+    LowerOperator, "lower", Middle, { value: positional(StringLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for LowerOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::String(evaluate_string(&self.value, context)?.to_lowercase()))
+    }
+}
+
+native_operator! {
+    /// Removes leading and trailing whitespace.
+    TrimOperator, "trim", Middle, { value: positional(StringLiteral | Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for TrimOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::String(evaluate_string(&self.value, context)?.trim().to_owned()))
+    }
+}
+
+native_operator! {
+    /// At most `length` characters from `start`, as in `(( substr &x 0 8 ))`, or all of them when no length is given.
+    /// Indices count characters, not bytes, and are clamped to the string.
+    SubstrOperator, "substr", Middle, {
+        value: positional(StringLiteral | Reference | Expr | Fallback),
+        start: positional(NumberLiteral | Reference | Expr | Fallback),
+        length: optional(NumberLiteral | Reference | Expr | Fallback),
+    }
+}
+
+impl OperatorPayload for SubstrOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let value = evaluate_string(&self.value, context)?;
+        let start = evaluate_index(&self.start, context)?;
+        let characters = value.chars().skip(start);
+        Ok(OperatorData::String(match &self.length {
+            Some(length) => characters.take(evaluate_index(length, context)?).collect(),
+            None => characters.collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::*;
+
+    #[test]
+    fn test_string_operators() {
+        let mut data = document(r#"
+service: {name: " Billing-API ", version: v12.3, hosts: "a.example,b.example"}
+trimmed: (( trim &service.name ))
+name: (( lower (replace (trim &service.name) "-" "_") ))
+shout: (( upper &service.version ))
+major: (( regex-replace &service.version '^v(\d+)\..*$' "$1" ))
+release: (( regex-replace &service.version "^v(\d+)" "$1" ))
+hosts: (( split &service.hosts "," ))
+short: (( substr (trim &service.name) 0 7 ))
+tail: (( substr &service.version 1 ))
+clamped: (( substr "ab" 1 10 ))
+"#);
+        data.execute_operators().unwrap();
+
+        assert_eq!(string_at(&data, &["trimmed"]), "Billing-API");
+        assert_eq!(string_at(&data, &["name"]), "billing_api");
+        assert_eq!(string_at(&data, &["shout"]), "V12.3");
+        assert_eq!(string_at(&data, &["major"]), "12");
+        assert_eq!(string_at(&data, &["release"]), "12.3");
+        assert_eq!(string_at(&data, &["short"]), "Billing");
+        assert_eq!(string_at(&data, &["tail"]), "12.3");
+        assert_eq!(string_at(&data, &["clamped"]), "b");
+        assert_eq!(yaml_at(&data, &["hosts"]), "- a.example\n- b.example\n");
+
+        // Patterns are compiled when parsing.
+        assert!(parsing_error("a: (( regex-replace &b \"(\" \"\" ))").starts_with("at 'a': regex-replace: argument 2 is not a valid regex, "));
+        assert!(parsing_error("a: (( regex-replace &b &c \"\" ))").starts_with("at 'a': regex-replace: argument 2 expected string literal, found reference &c"));
+    }
+}
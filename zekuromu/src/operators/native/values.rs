@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::{data::{OperatorData, operators::Keyword}, operators::{EvaluationContext, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload}};
+
+native_operator! {
+    /// An empty mapping, sequence or string, as in `(( empty hash ))`, to clear an inherited value.
+    EmptyOperator, "empty", Middle, { kind: positional(Keyword) in [Hash, Array, String] }
+}
+
+impl OperatorPayload for EmptyOperator {
+    fn execute(&self, _context: &EvaluationContext) -> OperatorExecutionResult {
+        match self.kind {
+            Keyword::Hash => Ok(OperatorData::Mapping(HashMap::new())),
+            Keyword::Array => Ok(OperatorData::Sequence(vec![])),
+            Keyword::String => Ok(OperatorData::String(String::new())),
+            _ => unreachable!("keywords are checked when parsing"),
+        }
+    }
+}
+
+native_operator! {
+    /// Null, as in `(( null ))`, to clear an inherited value of any kind.
+    NullOperator, "null", Middle, {}
+}
+
+impl OperatorPayload for NullOperator {
+    fn execute(&self, _context: &EvaluationContext) -> OperatorExecutionResult {
+        Ok(OperatorData::Null)
+    }
+}
+
+native_operator! {
+    /// The opposite of a boolean.
+    NegateOperator, "negate", Middle, { value: positional(Reference | Expr | Fallback) }
+}
+
+impl OperatorPayload for NegateOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        match self.value.evaluate(context)? {
+            OperatorData::Boolean(inner) => Ok(OperatorData::Boolean(!inner)),
+            other => Err(OperatorExecutionErrorReason::OtherError(format!("expected a boolean to negate, found {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::OperatorData, test_helpers::*};
+
+    #[test]
+    fn test_values() {
+        let base = raw_document("
+labels: {a: b}
+tags: [a]
+flag: true
+");
+        let overrides = raw_document("
+labels: (( empty hash ))
+tags: (( empty array ))
+name: (( empty string ))
+nothing: (( null ))
+flag: (( negate &enabled ))
+enabled: true
+");
+        let mut data: OperatorData = base.recursive_merge(overrides).into_raw_operator_data().try_into().unwrap();
+        data.execute_operators().unwrap();
+
+        assert_eq!(yaml_at(&data, &[]), "enabled: true\nflag: false\nlabels: {}\nname: ''\nnothing: null\ntags: []\n");
+
+        assert!(parsing_error("a: (( empty json ))").starts_with("at 'a': empty: argument 1 expected hash, array or string, found json"));
+    }
+}
@@ -0,0 +1,363 @@
+//! Registration of operators defined outside of this crate.
+//! A registered operator is looked up by its name, has its arguments checked against its schema,
+//! and is then built by its payload factory.
+
+use std::{collections::{HashMap, HashSet}, fmt::Display, path::{Path, PathBuf}, rc::Rc};
+
+use crate::{data::operators::{Expr, REPLACE_MARKER}, operators::{Operator, OperatorParsingError, OperatorParsingErrorReason, OperatorPayload, OperatorPriorityRank, OperatorSource, native::NativeOperator, signature::Signature}};
+
+/// Builds the payload of an operator out of its already checked expression.
+/// The registry is the one parsing the operator, for nested calls, see [`crate::operators::OperatorArgument`].
+pub type PayloadFactory = Rc<dyn Fn(&Expr, &OperatorRegistry) -> Result<Rc<dyn OperatorPayload>, OperatorParsingErrorReason>>;
+
+#[derive(Clone)]
+pub struct OperatorRegistration {
+    name: String,
+    source: OperatorSource,
+    signature: Signature,
+    priority: OperatorPriorityRank,
+    factory: PayloadFactory,
+}
+
+impl OperatorRegistration {
+    /// `signature` can also be given as the kinds of the positional arguments the operator expects.
+    pub fn new<F>(name: impl Into<String>, signature: impl Into<Signature>, priority: OperatorPriorityRank, factory: F) -> Self
+    where F: Fn(&Expr, &OperatorRegistry) -> Result<Rc<dyn OperatorPayload>, OperatorParsingErrorReason> + 'static
+    {
+        let name = name.into();
+        OperatorRegistration {
+            source: OperatorSource::Registered(name.clone()),
+            name,
+            signature: signature.into(),
+            priority,
+            factory: Rc::new(factory),
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    pub(crate) fn with_source(mut self, source: OperatorSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    pub fn priority(&self) -> OperatorPriorityRank {
+        self.priority
+    }
+
+    fn try_parsing_operator(&self, expr: &Expr, registry: &OperatorRegistry) -> Result<Operator, OperatorParsingErrorReason> {
+        self.signature.check(&expr.arguments)?;
+
+        Ok(Operator {
+            source: self.source.clone(),
+            payload: (self.factory)(expr, registry)?,
+            priority: self.priority,
+            expr: Rc::new(expr.clone()),
+        })
+    }
+}
+
+impl std::fmt::Debug for OperatorRegistration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OperatorRegistration")
+            .field("name", &self.name)
+            .field("source", &self.source)
+            .field("signature", &self.signature)
+            .field("priority", &self.priority)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OperatorRegistrationError {
+    CollidesWithNative(NativeOperator),
+    AlreadyRegistered(String),
+    /// The name means something else where operators are written, as the replace marker does.
+    Reserved(String),
+}
+
+impl Display for OperatorRegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperatorRegistrationError::CollidesWithNative(native) => write!(f, "'{}' is already the name of a native operator", native.name()),
+            OperatorRegistrationError::AlreadyRegistered(name) => write!(f, "an operator named '{}' is already registered", name),
+            OperatorRegistrationError::Reserved(name) => write!(f, "'{}' is reserved", name),
+        }
+    }
+}
+
+/// Native operators are always available, and cannot be shadowed by registered ones.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorRegistry {
+    operators: HashMap<String, OperatorRegistration>,
+    file_root: Option<PathBuf>,
+    environment: Option<Rc<HashMap<String, String>>>,
+    disabled: HashSet<NativeOperator>,
+}
+
+impl OperatorRegistry {
+    pub fn new() -> Self {
+        OperatorRegistry::default()
+    }
+
+    /// Operators reading files, such as `file` and `load`, can only read those under `root`,
+    /// which relative paths are relative to when the data was not read from a file.
+    pub fn set_file_root(&mut self, root: impl Into<PathBuf>) {
+        self.file_root = Some(root.into());
+    }
+
+    /// `None` when files can be read from anywhere.
+    pub fn file_root(&self) -> Option<&Path> {
+        self.file_root.as_deref()
+    }
+
+    /// Operators reading environment variables, such as `env`, only see `variables`, instead of the process environment.
+    pub fn set_environment(&mut self, variables: HashMap<String, String>) {
+        self.environment = Some(Rc::new(variables));
+    }
+
+    /// `None` when variables are read from the process environment.
+    pub(crate) fn environment(&self) -> Option<Rc<HashMap<String, String>>> {
+        self.environment.clone()
+    }
+
+    /// Any use of `native` becomes a parsing error, as with `env` for hermetic builds.
+    pub fn disable(&mut self, native: NativeOperator) {
+        self.disabled.insert(native);
+    }
+
+    pub fn is_disabled(&self, native: NativeOperator) -> bool {
+        self.disabled.contains(&native)
+    }
+
+    /// Names must differ from native operators, and from the replace marker.
+    pub fn register(&mut self, registration: OperatorRegistration) -> Result<(), OperatorRegistrationError> {
+        if let Some(native) = NativeOperator::from_name(&registration.name) {
+            return Err(OperatorRegistrationError::CollidesWithNative(native))
+        }
+
+        if registration.name == REPLACE_MARKER {
+            return Err(OperatorRegistrationError::Reserved(registration.name))
+        }
+
+        if self.operators.contains_key(&registration.name) {
+            return Err(OperatorRegistrationError::AlreadyRegistered(registration.name))
+        }
+
+        self.operators.insert(registration.name.clone(), registration);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OperatorRegistration> {
+        self.operators.get(name)
+    }
+
+    /// Whether `name` is a native or registered operator.
+    pub fn contains(&self, name: &str) -> bool {
+        NativeOperator::from_name(name).is_some() || self.operators.contains_key(name)
+    }
+
+    /// Native and registered operators, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = NativeOperator::ALL.iter()
+            .map(|native| native.name())
+            .chain(self.operators.keys().map(String::as_str))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The closest known operator name, when close enough to be a likely typo of `name`.
+    pub fn suggest(&self, name: &str) -> Option<&str> {
+        let threshold = (name.chars().count() / 3).max(1);
+        self.names().into_iter()
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= threshold)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    pub fn try_parsing_operator(&self, expr: &Expr) -> Result<Operator, OperatorParsingError> {
+        match NativeOperator::try_parsing_operator(expr, self) {
+            Err((None, OperatorParsingErrorReason::NoneMatched)) => {},
+            native => return native,
+        }
+
+        let registration = self.get(&expr.name.0)
+            .ok_or_else(|| (None, OperatorParsingErrorReason::UnknownOperator {
+                suggestion: self.suggest(&expr.name.0).map(str::to_owned),
+            }))?;
+
+        registration.try_parsing_operator(expr, self)
+            .map_err(|reason| (Some(registration.source.clone()), reason))
+    }
+
+    /// Parses the nested call found at `position` in the arguments of another.
+    pub fn try_parsing_nested_operator(&self, expr: &Expr, position: usize) -> Result<Operator, OperatorParsingErrorReason> {
+        self.try_parsing_operator(expr)
+            .map_err(|(_, reason)| OperatorParsingErrorReason::InNestedOperator {
+                position,
+                name: expr.name.0.clone(),
+                reason: Box::new(reason),
+            })
+    }
+}
+
+/// Levenshtein distance, counted in characters.
+fn edit_distance(from: &str, to: &str) -> usize {
+    let to: Vec<char> = to.chars().collect();
+    let mut previous: Vec<usize> = (0..=to.len()).collect();
+
+    for (i, from_char) in from.chars().enumerate() {
+        let mut current = vec![i + 1; to.len() + 1];
+        for (j, to_char) in to.iter().enumerate() {
+            let substitution = previous[j] + usize::from(from_char != *to_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[to.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::{data::{DataKey, DataKeyPath, OperatorData}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, signature::ArgumentKind}};
+
+    #[derive(Debug)]
+    struct DoubleOperator {
+        value: OperatorArgument,
+    }
+
+    impl OperatorPayload for DoubleOperator {
+        fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+            match self.value.evaluate(context)? {
+                OperatorData::Number(inner) => Ok(OperatorData::Number(inner * 2.0)),
+                _ => Err(OperatorExecutionErrorReason::OtherError("not a number".to_owned())),
+            }
+        }
+    }
+
+    fn double_registration(name: &str) -> OperatorRegistration {
+        let signature = Signature::new().positional(&[ArgumentKind::Reference, ArgumentKind::Expr]);
+        OperatorRegistration::new(name, signature, OperatorPriorityRank::Middle, |expr, registry| {
+            Ok(Rc::new(DoubleOperator { value: OperatorArgument::try_from_argument(&expr.arguments[0], 0, registry)? }))
+        })
+    }
+
+    fn parse(yaml: &str, registry: &OperatorRegistry) -> Result<OperatorData, OperatorParsingError> {
+        let raw = crate::raw::yaml::parse_as_raw_data(&mut BufReader::new(yaml.as_bytes())).unwrap();
+        raw.into_raw_operator_data().try_into_operator_data(registry)
+            .map_err(|error| (error.source, error.reason))
+    }
+
+    #[test]
+    fn test_registered_operator() {
+        let mut registry = OperatorRegistry::new();
+        registry.register(double_registration("double")).unwrap();
+
+        let mut data = parse("a: 21\nb: (( double &a ))\nc: (( grab &b ))", &registry).unwrap();
+        data.execute_operators().unwrap();
+
+        assert!(matches!(data.get(&DataKeyPath::from(DataKey::from("c"))), Some(OperatorData::Number(inner)) if *inner == 42.0));
+    }
+
+    #[test]
+    fn test_registration_collisions() {
+        let mut registry = OperatorRegistry::new();
+
+        assert_eq!(
+            registry.register(double_registration("grab")).unwrap_err(),
+            OperatorRegistrationError::CollidesWithNative(NativeOperator::Grab)
+        );
+
+        registry.register(double_registration("double")).unwrap();
+        assert_eq!(
+            registry.register(double_registration("double")).unwrap_err(),
+            OperatorRegistrationError::AlreadyRegistered("double".to_owned())
+        );
+
+        assert_eq!(
+            registry.register(double_registration("replace-subtree")).unwrap_err(),
+            OperatorRegistrationError::Reserved("replace-subtree".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_registered_operator_parsing_errors() {
+        let mut registry = OperatorRegistry::new();
+        registry.register(double_registration("double")).unwrap();
+
+        assert!(matches!(
+            parse("a: (( double ))", &registry),
+            Err((Some(OperatorSource::Registered(name)), OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum: 1, maximum: Some(1), found: 0 })) if name == "double"
+        ));
+        assert!(matches!(
+            parse("a: (( double \"a\" ))", &registry),
+            Err((Some(OperatorSource::Registered(_)), OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position: 0, .. }))
+        ));
+        assert!(matches!(
+            parse("a: (( triple &a ))", &registry),
+            Err((None, OperatorParsingErrorReason::UnknownOperator { suggestion: None }))
+        ));
+        assert!(matches!(
+            parse("a: (( doubel &a ))", &registry),
+            Err((None, OperatorParsingErrorReason::UnknownOperator { suggestion: Some(name) })) if name == "double"
+        ));
+        assert!(matches!(
+            parse("a: (( double &a ))", &OperatorRegistry::new()),
+            Err((None, OperatorParsingErrorReason::UnknownOperator { suggestion: None }))
+        ));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut registry = OperatorRegistry::new();
+        registry.register(double_registration("double")).unwrap();
+
+        assert_eq!(edit_distance("grab", "grab"), 0);
+        assert_eq!(edit_distance("grabb", "grab"), 1);
+        assert_eq!(edit_distance("gerb", "grab"), 2);
+
+        assert_eq!(registry.suggest("grabb"), Some("grab"));
+        assert_eq!(registry.suggest("expcet"), Some("expect"));
+        assert_eq!(registry.suggest("doubel"), Some("double"));
+        assert_eq!(registry.suggest("concat"), None);
+        assert!(registry.contains("param") && registry.contains("double") && !registry.contains("triple"));
+    }
+
+    #[test]
+    fn test_nested_operators() {
+        let mut registry = OperatorRegistry::new();
+        registry.register(double_registration("double")).unwrap();
+
+        let mut data = parse("
+a: 21
+b: (( double (double &a) ))
+c: (( double (grab &d) ))
+d: (( double (double &a) ))
+", &registry).unwrap();
+        data.execute_operators().unwrap();
+
+        assert!(matches!(data.get(&DataKeyPath::from(DataKey::from("b"))), Some(OperatorData::Number(inner)) if *inner == 84.0));
+        assert!(matches!(data.get(&DataKeyPath::from(DataKey::from("c"))), Some(OperatorData::Number(inner)) if *inner == 168.0));
+
+        let raw = crate::raw::yaml::parse_as_raw_data(&mut BufReader::new("a: (( double (double (grab \"b\")) ))".as_bytes())).unwrap();
+        let error = raw.into_raw_operator_data().try_into_operator_data(&registry).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "at 'a': double: argument 1, double: argument 1, grab: argument 1 expected reference or fallback chain, found string literal \"b\"\n\
+             (( double (double (grab \"b\")) ))\n                        ^^^"
+        );
+    }
+}
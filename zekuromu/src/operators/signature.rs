@@ -0,0 +1,193 @@
+//! Arguments an operator accepts: positional ones first, then optional ones, then possibly a variadic one.
+//! Each argument allows one or several kinds.
+
+use std::fmt::Display;
+
+use crate::{data::operators::Argument, operators::OperatorParsingErrorReason};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    StringLiteral,
+    NumberLiteral,
+    BooleanLiteral,
+    Reference,
+    /// A nested call.
+    Expr,
+    /// Alternatives separated by `||`.
+    Fallback,
+    Keyword,
+}
+
+impl ArgumentKind {
+    pub fn of(argument: &Argument) -> ArgumentKind {
+        match argument {
+            Argument::StringLiteral(_) => ArgumentKind::StringLiteral,
+            Argument::NumberLiteral(_) => ArgumentKind::NumberLiteral,
+            Argument::BooleanLiteral(_) => ArgumentKind::BooleanLiteral,
+            Argument::Reference(_) => ArgumentKind::Reference,
+            Argument::Expr(_) => ArgumentKind::Expr,
+            Argument::Fallback(_) => ArgumentKind::Fallback,
+            Argument::Keyword(_) => ArgumentKind::Keyword,
+        }
+    }
+
+    pub fn matches(&self, argument: &Argument) -> bool {
+        *self == ArgumentKind::of(argument)
+    }
+}
+
+impl Display for ArgumentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgumentKind::StringLiteral => write!(f, "string literal"),
+            ArgumentKind::NumberLiteral => write!(f, "number literal"),
+            ArgumentKind::BooleanLiteral => write!(f, "boolean literal"),
+            ArgumentKind::Reference => write!(f, "reference"),
+            ArgumentKind::Expr => write!(f, "operator call"),
+            ArgumentKind::Fallback => write!(f, "fallback chain"),
+            ArgumentKind::Keyword => write!(f, "keyword"),
+        }
+    }
+}
+
+/// Displays as `string literal or reference`.
+pub struct ArgumentKinds<'a>(pub &'a [ArgumentKind]);
+
+impl Display for ArgumentKinds<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let joined = self.0.iter()
+            .map(|kind| kind.to_string())
+            .collect::<Vec<String>>()
+            .join(" or ");
+        write!(f, "{}", joined)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Signature {
+    positional: Vec<Vec<ArgumentKind>>,
+    optional: Vec<Vec<ArgumentKind>>,
+    variadic: Option<Vec<ArgumentKind>>,
+}
+
+impl Signature {
+    /// Accepts no argument.
+    pub fn new() -> Self {
+        Signature::default()
+    }
+
+    pub fn positional(mut self, kinds: &[ArgumentKind]) -> Self {
+        assert!(self.optional.is_empty() && self.variadic.is_none(), "positional arguments must come first");
+        self.positional.push(kinds.to_vec());
+        self
+    }
+
+    pub fn optional(mut self, kinds: &[ArgumentKind]) -> Self {
+        assert!(self.variadic.is_none(), "optional arguments must come before the variadic one");
+        self.optional.push(kinds.to_vec());
+        self
+    }
+
+    /// Any number of trailing arguments, including none.
+    pub fn variadic(mut self, kinds: &[ArgumentKind]) -> Self {
+        assert!(self.variadic.is_none(), "there can only be one variadic argument");
+        self.variadic = Some(kinds.to_vec());
+        self
+    }
+
+    pub fn minimum(&self) -> usize {
+        self.positional.len()
+    }
+
+    /// `None` when variadic.
+    pub fn maximum(&self) -> Option<usize> {
+        match self.variadic {
+            Some(_) => None,
+            None => Some(self.positional.len() + self.optional.len()),
+        }
+    }
+
+    /// Kinds allowed for the argument at `position`, if there can be one there.
+    pub fn kinds_at(&self, position: usize) -> Option<&[ArgumentKind]> {
+        self.positional.iter()
+            .chain(&self.optional)
+            .nth(position)
+            .or(self.variadic.as_ref())
+            .map(Vec::as_slice)
+    }
+
+    pub fn check(&self, arguments: &[Argument]) -> Result<(), OperatorParsingErrorReason> {
+        let found = arguments.len();
+        if found < self.minimum() || self.maximum().is_some_and(|maximum| found > maximum) {
+            return Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch {
+                minimum: self.minimum(),
+                maximum: self.maximum(),
+                found,
+            })
+        }
+
+        for (position, argument) in arguments.iter().enumerate() {
+            let expected = self.kinds_at(position).unwrap_or_default();
+            if !expected.iter().any(|kind| kind.matches(argument)) {
+                return Err(OperatorParsingErrorReason::ArgumentTypeDoesNotMatch {
+                    position,
+                    expected: expected.to_vec(),
+                    found: ArgumentKind::of(argument),
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Each kind is a positional argument.
+impl From<Vec<ArgumentKind>> for Signature {
+    fn from(value: Vec<ArgumentKind>) -> Self {
+        value.into_iter().fold(Signature::new(), |signature, kind| signature.positional(&[kind]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::operators::{NumberLiteral, StringLiteral};
+
+    fn string() -> Argument {
+        Argument::StringLiteral(StringLiteral("a".to_owned()))
+    }
+
+    fn number() -> Argument {
+        Argument::NumberLiteral(NumberLiteral::Float("1.0".to_owned()))
+    }
+
+    #[test]
+    fn test_check() {
+        let signature = Signature::new()
+            .positional(&[ArgumentKind::StringLiteral])
+            .optional(&[ArgumentKind::NumberLiteral])
+            .variadic(&[ArgumentKind::StringLiteral, ArgumentKind::NumberLiteral]);
+
+        assert!(signature.check(&[string()]).is_ok());
+        assert!(signature.check(&[string(), number()]).is_ok());
+        assert!(signature.check(&[string(), number(), string(), number()]).is_ok());
+
+        assert!(matches!(
+            signature.check(&[]),
+            Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum: 1, maximum: None, found: 0 })
+        ));
+        assert!(matches!(
+            signature.check(&[number()]),
+            Err(OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position: 0, found: ArgumentKind::NumberLiteral, .. })
+        ));
+        assert!(matches!(
+            signature.check(&[string(), string()]),
+            Err(OperatorParsingErrorReason::ArgumentTypeDoesNotMatch { position: 1, found: ArgumentKind::StringLiteral, .. })
+        ));
+
+        assert!(matches!(
+            Signature::from(vec![ArgumentKind::StringLiteral]).check(&[string(), string()]),
+            Err(OperatorParsingErrorReason::ArgumentsLengthDoesNotMatch { minimum: 1, maximum: Some(1), found: 2 })
+        ));
+    }
+}
@@ -0,0 +1,534 @@
+//! Operators provided by WebAssembly modules, loaded at runtime.
+//!
+//! Modules are not given any import, so they can only compute on what they are handed,
+//! and every call is bounded by an amount of fuel and a memory limit.
+//!
+//! A module must export:
+//! - `memory`, its linear memory;
+//! - `zekuromu_alloc(len: i32) -> i32`, reserving `len` bytes for the host to write into;
+//! - `zekuromu_manifest() -> i64`, the operators it provides;
+//! - `zekuromu_execute(ptr: i32, len: i32) -> i64`, executing the call written at `ptr`.
+//!
+//! Buffers returned to the host are UTF-8 JSON, packed in an `i64` as `ptr << 32 | len`.
+//!
+//! The manifest is a list of `{"name": "...", "arguments": [...], "priority": "..."}`,
+//! where arguments are any of `"string"`, `"number"`, `"boolean"`, `"reference"`, `"operator"` for a nested call,
+//! `"fallback"` for `||`-separated alternatives, `"keyword"` for bare words such as `hash`,
+//! and the optional priority one of `"first"`, `"after-first"`, `"middle"`, `"before-last"`, `"last"`.
+//!
+//! A call is `{"operator": "...", "path": "...", "arguments": [...]}`, arguments being one of
+//! `{"string": "..."}`, `{"number": 1.0}`, `{"boolean": true}`, `{"keyword": "..."}`, `{"reference": "a.b", "value": ...}` or `{"value": ...}`,
+//! references, nested calls and fallbacks being evaluated by the host beforehand.
+//! The module answers with either `{"ok": value}` or `{"error": "message"}`.
+
+use std::{cell::RefCell, collections::HashMap, fmt::Display, path::Path, rc::Rc};
+
+use serde_json::Value;
+use wasmi::{Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::{data::{DataKey, OperatorData, RawData, operators::Expr}, operators::{EvaluationContext, OperatorArgument, OperatorExecutionErrorReason, OperatorExecutionResult, OperatorPayload, OperatorPriorityRank, OperatorSource, registry::{OperatorRegistration, OperatorRegistrationError, OperatorRegistry}, signature::ArgumentKind}};
+
+/// Instructions a single call may execute before being interrupted.
+const FUEL_PER_CALL: u64 = 100_000_000;
+/// Upper bound on the linear memory of a module.
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Upper bound on a buffer returned by a module.
+const MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum WasmPluginError {
+    Io(String),
+    InvalidModule(String),
+    /// Plugins are sandboxed, so they cannot import anything.
+    HasImports,
+    MissingExport(&'static str),
+    InvalidManifest(String),
+}
+
+impl Display for WasmPluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WasmPluginError::Io(message) => write!(f, "could not read module, {}", message),
+            WasmPluginError::InvalidModule(message) => write!(f, "invalid module, {}", message),
+            WasmPluginError::HasImports => write!(f, "module must not import anything"),
+            WasmPluginError::MissingExport(name) => write!(f, "module does not export '{}'", name),
+            WasmPluginError::InvalidManifest(message) => write!(f, "invalid manifest, {}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasmOperatorManifest {
+    pub name: String,
+    pub arguments: Vec<ArgumentKind>,
+    pub priority: OperatorPriorityRank,
+}
+
+struct WasmInstance {
+    module: Rc<Module>,
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    manifest: TypedFunc<(), i64>,
+    execute: TypedFunc<(i32, i32), i64>,
+}
+
+impl WasmInstance {
+    /// With its own memory and globals, as when the module was first loaded.
+    fn new(module: Rc<Module>) -> Result<WasmInstance, WasmPluginError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .build();
+        let mut store = Store::new(module.engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_CALL)
+            .map_err(|error| WasmPluginError::InvalidModule(error.to_string()))?;
+
+        let instance = Linker::<StoreLimits>::new(module.engine())
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|error| WasmPluginError::InvalidModule(error.to_string()))?;
+
+        let memory = instance.get_memory(&store, "memory")
+            .ok_or(WasmPluginError::MissingExport("memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "zekuromu_alloc")
+            .map_err(|_| WasmPluginError::MissingExport("zekuromu_alloc"))?;
+        let manifest = instance.get_typed_func::<(), i64>(&store, "zekuromu_manifest")
+            .map_err(|_| WasmPluginError::MissingExport("zekuromu_manifest"))?;
+        let execute = instance.get_typed_func::<(i32, i32), i64>(&store, "zekuromu_execute")
+            .map_err(|_| WasmPluginError::MissingExport("zekuromu_execute"))?;
+
+        Ok(WasmInstance { module, store, memory, alloc, manifest, execute })
+    }
+
+    fn read_packed(&self, packed: i64) -> Result<Vec<u8>, String> {
+        let ptr = (packed as u64 >> 32) as usize;
+        let len = (packed as u64 & 0xffff_ffff) as usize;
+
+        if len > MAX_OUTPUT_BYTES {
+            return Err(format!("buffer of {} bytes exceeds the limit of {} bytes", len, MAX_OUTPUT_BYTES))
+        }
+
+        // Checked before copying anything, as both halves are chosen by the module.
+        let data = self.memory.data(&self.store);
+        ptr.checked_add(len)
+            .and_then(|end| data.get(ptr..end))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| format!("buffer at {} of {} bytes lies outside of memory", ptr, len))
+    }
+
+    fn call(&mut self, input: &[u8]) -> Result<Vec<u8>, String> {
+        let len = i32::try_from(input.len())
+            .map_err(|_| "call is too large".to_owned())?;
+
+        self.store.set_fuel(FUEL_PER_CALL)
+            .map_err(|error| error.to_string())?;
+
+        let packed = match self.execute_at(input, len) {
+            Ok(packed) => packed,
+            Err(trap) => return Err(self.reset(trap)),
+        };
+        self.read_packed(packed)
+    }
+
+    fn execute_at(&mut self, input: &[u8], len: i32) -> Result<i64, String> {
+        let ptr = self.alloc.call(&mut self.store, len)
+            .map_err(|error| error.to_string())?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, input)
+            .map_err(|error| error.to_string())?;
+        self.execute.call(&mut self.store, (ptr, len))
+            .map_err(|error| error.to_string())
+    }
+
+    // A trap can leave memory and globals half updated, so later calls get a fresh instance.
+    fn reset(&mut self, trap: String) -> String {
+        match WasmInstance::new(self.module.clone()) {
+            Ok(fresh) => {
+                *self = fresh;
+                trap
+            },
+            Err(error) => format!("{}, and the module could not be instantiated again, {}", trap, error),
+        }
+    }
+}
+
+/// A loaded module, whose operators share a single instance, replaced by a fresh one after a trap.
+pub struct WasmPlugin {
+    name: String,
+    instance: Rc<RefCell<WasmInstance>>,
+    operators: Vec<WasmOperatorManifest>,
+}
+
+impl WasmPlugin {
+    pub fn load(path: &Path) -> Result<WasmPlugin, WasmPluginError> {
+        let bytes = std::fs::read(path)
+            .map_err(|error| WasmPluginError::Io(error.to_string()))?;
+        WasmPlugin::from_bytes(path.display().to_string(), &bytes)
+    }
+
+    /// `name` identifies the plugin in errors.
+    pub fn from_bytes(name: impl Into<String>, wasm: &[u8]) -> Result<WasmPlugin, WasmPluginError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+
+        let module = Module::new(&engine, wasm)
+            .map_err(|error| WasmPluginError::InvalidModule(error.to_string()))?;
+
+        if module.imports().next().is_some() {
+            return Err(WasmPluginError::HasImports)
+        }
+
+        let mut wasm_instance = WasmInstance::new(Rc::new(module))?;
+
+        let packed = wasm_instance.manifest.call(&mut wasm_instance.store, ())
+            .map_err(|error| WasmPluginError::InvalidManifest(error.to_string()))?;
+        let operators = wasm_instance.read_packed(packed)
+            .and_then(|buffer| parse_manifest(&buffer))
+            .map_err(WasmPluginError::InvalidManifest)?;
+
+        Ok(WasmPlugin {
+            name: name.into(),
+            instance: Rc::new(RefCell::new(wasm_instance)),
+            operators,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn operators(&self) -> &[WasmOperatorManifest] {
+        &self.operators
+    }
+
+    /// Registers every operator of the plugin, stopping at the first one that cannot be.
+    pub fn register_into(&self, registry: &mut OperatorRegistry) -> Result<(), OperatorRegistrationError> {
+        for manifest in &self.operators {
+            let plugin = self.name.clone();
+            let name = manifest.name.clone();
+            let instance = self.instance.clone();
+
+            let registration = OperatorRegistration::new(&manifest.name, manifest.arguments.clone(), manifest.priority, move |expr: &Expr, registry: &OperatorRegistry| {
+                let arguments = expr.arguments.iter()
+                    .enumerate()
+                    .map(|(position, argument)| OperatorArgument::try_from_argument(argument, position, registry))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Rc::new(WasmOperator {
+                    plugin: plugin.clone(),
+                    name: name.clone(),
+                    arguments,
+                    instance: instance.clone(),
+                }) as Rc<dyn OperatorPayload>)
+            }).with_source(OperatorSource::Wasm { plugin: self.name.clone(), name: manifest.name.clone() });
+
+            registry.register(registration)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for WasmPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmPlugin")
+            .field("name", &self.name)
+            .field("operators", &self.operators)
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse_manifest(buffer: &[u8]) -> Result<Vec<WasmOperatorManifest>, String> {
+    let value: Value = serde_json::from_slice(buffer)
+        .map_err(|error| error.to_string())?;
+
+    let entries = value.as_array()
+        .ok_or("expected a list of operators".to_owned())?;
+
+    entries.iter().map(|entry| {
+        let name = entry.get("name")
+            .and_then(Value::as_str)
+            .ok_or("expected an operator name".to_owned())?;
+
+        let arguments = match entry.get("arguments") {
+            None => Vec::new(),
+            Some(Value::Array(kinds)) => kinds.iter()
+                .map(|kind| match kind.as_str() {
+                    Some("string") => Ok(ArgumentKind::StringLiteral),
+                    Some("number") => Ok(ArgumentKind::NumberLiteral),
+                    Some("boolean") => Ok(ArgumentKind::BooleanLiteral),
+                    Some("reference") => Ok(ArgumentKind::Reference),
+                    Some("operator") => Ok(ArgumentKind::Expr),
+                    Some("fallback") => Ok(ArgumentKind::Fallback),
+                    Some("keyword") => Ok(ArgumentKind::Keyword),
+                    _ => Err(format!("unknown argument kind {} for '{}'", kind, name)),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(format!("expected a list of arguments for '{}'", name)),
+        };
+
+        let priority = match entry.get("priority").map(|priority| priority.as_str()) {
+            None | Some(Some("middle")) => OperatorPriorityRank::Middle,
+            Some(Some("first")) => OperatorPriorityRank::First,
+            Some(Some("after-first")) => OperatorPriorityRank::AfterFirst,
+            Some(Some("before-last")) => OperatorPriorityRank::BeforeLast,
+            Some(Some("last")) => OperatorPriorityRank::Last,
+            Some(_) => return Err(format!("unknown priority for '{}'", name)),
+        };
+
+        Ok(WasmOperatorManifest { name: name.to_owned(), arguments, priority })
+    }).collect()
+}
+
+// Fails on what JSON cannot hold, rather than passing it as null.
+fn data_as_json(data: &OperatorData) -> Result<Value, OperatorExecutionErrorReason> {
+    match data {
+        OperatorData::Null => Ok(Value::Null),
+        OperatorData::Operator(inner) => Err(OperatorExecutionErrorReason::OtherError(
+            format!("cannot pass '{}' to a plugin, as it has not been evaluated", inner.expr())
+        )),
+        OperatorData::Boolean(inner) => Ok(Value::Bool(*inner)),
+        OperatorData::Number(inner) => serde_json::Number::from_f64(*inner)
+            .map(Value::Number)
+            .ok_or_else(|| OperatorExecutionErrorReason::OtherError(format!("cannot pass {} to a plugin, as JSON has no such number", inner))),
+        OperatorData::String(inner) => Ok(Value::String(inner.clone())),
+        OperatorData::Sequence(inner) => inner.iter()
+            .map(data_as_json)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        OperatorData::Mapping(inner) => inner.iter()
+            .map(|(key, value)| Ok((key.as_str().to_owned(), data_as_json(value)?)))
+            .collect::<Result<_, _>>()
+            .map(Value::Object),
+    }
+}
+
+fn json_as_data(value: Value) -> RawData {
+    match value {
+        Value::Null => RawData::Null,
+        Value::Bool(inner) => RawData::Boolean(inner),
+        Value::Number(inner) => inner.as_f64().map(RawData::Number).unwrap_or(RawData::Null),
+        Value::String(inner) => RawData::String(inner),
+        Value::Array(inner) => RawData::Sequence(inner.into_iter().map(json_as_data).collect()),
+        Value::Object(inner) => RawData::Mapping(
+            inner.into_iter().map(|(key, value)| (DataKey::from(key), json_as_data(value))).collect::<HashMap<_, _>>()
+        ),
+    }
+}
+
+pub struct WasmOperator {
+    plugin: String,
+    name: String,
+    arguments: Vec<OperatorArgument>,
+    instance: Rc<RefCell<WasmInstance>>,
+}
+
+impl WasmOperator {
+    fn arguments_as_json(&self, context: &EvaluationContext) -> Result<Vec<Value>, OperatorExecutionErrorReason> {
+        self.arguments.iter().map(|argument| match argument {
+            OperatorArgument::StringLiteral(inner) => Ok(serde_json::json!({ "string": inner.0 })),
+            OperatorArgument::Keyword(inner) => Ok(serde_json::json!({ "keyword": inner.as_str() })),
+            OperatorArgument::BooleanLiteral(inner) => Ok(serde_json::json!({ "boolean": inner.0 })),
+            OperatorArgument::NumberLiteral(inner) => f64::try_from(inner)
+                .map(|number| serde_json::json!({ "number": number }))
+                .map_err(|error| OperatorExecutionErrorReason::OtherError(format!("invalid number literal, {}", error))),
+            OperatorArgument::Reference(inner) => {
+                let (at, value) = context.resolve(inner)
+                    .map_err(OperatorExecutionErrorReason::ReferenceUnavailable)?;
+                Ok(serde_json::json!({ "reference": at.to_string(), "value": data_as_json(value)? }))
+            },
+            OperatorArgument::Operator(_) | OperatorArgument::Fallback(_) => Ok(serde_json::json!({ "value": data_as_json(&argument.evaluate(context)?)? })),
+        }).collect()
+    }
+
+    fn failed(&self, message: impl Display) -> OperatorExecutionErrorReason {
+        OperatorExecutionErrorReason::PluginFailed(format!("'{}' from '{}': {}", self.name, self.plugin, message))
+    }
+}
+
+impl OperatorPayload for WasmOperator {
+    fn execute(&self, context: &EvaluationContext) -> OperatorExecutionResult {
+        let call = serde_json::json!({
+            "operator": self.name,
+            "path": context.path.to_string(),
+            "arguments": self.arguments_as_json(context)?,
+        });
+
+        let output = self.instance.borrow_mut()
+            .call(call.to_string().as_bytes())
+            .map_err(|message| self.failed(message))?;
+
+        let mut answer: Value = serde_json::from_slice(&output)
+            .map_err(|error| self.failed(format!("invalid answer, {}", error)))?;
+
+        if let Some(value) = answer.get_mut("ok") {
+            return Ok(OperatorData::from(json_as_data(value.take())))
+        }
+
+        match answer.get("error").and_then(Value::as_str) {
+            Some(message) => Err(self.failed(message)),
+            None => Err(self.failed("answer has neither 'ok' nor 'error'")),
+        }
+    }
+}
+
+impl std::fmt::Debug for WasmOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmOperator")
+            .field("plugin", &self.plugin)
+            .field("name", &self.name)
+            .field("arguments", &self.arguments)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{path, raw_document};
+
+    fn escape(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    // Host writes calls at 1030, right after the `{"ok":` prefix, so that the answer echoes the call.
+    // `answer` is available at 2048.
+    fn module(manifest: &str, answer: &str, execute: &str) -> Vec<u8> {
+        let (manifest_escaped, answer_escaped) = (escape(manifest), escape(answer));
+        wat::parse_str(format!(r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{manifest_escaped}")
+                (data (i32.const 2048) "{answer_escaped}")
+                (data (i32.const 1024) "{{\"ok\":")
+                (func (export "zekuromu_alloc") (param i32) (result i32) (i32.const 1030))
+                (func (export "zekuromu_manifest") (result i64) (i64.const {len}))
+                (func (export "zekuromu_execute") (param $ptr i32) (param $len i32) (result i64) {execute})
+            )"#, len = manifest.len())).unwrap()
+    }
+
+    const ECHO: &str = r#"
+        (i32.store8 (i32.add (local.get $ptr) (local.get $len)) (i32.const 125))
+        (i64.or
+            (i64.shl (i64.const 1024) (i64.const 32))
+            (i64.extend_i32_u (i32.add (local.get $len) (i32.const 7))))"#;
+
+    fn execute(plugin: &WasmPlugin, yaml: &str) -> Result<OperatorData, Vec<OperatorExecutionErrorReason>> {
+        let mut registry = OperatorRegistry::new();
+        plugin.register_into(&mut registry).unwrap();
+
+        let mut data = raw_document(yaml).into_raw_operator_data().try_into_operator_data(&registry).unwrap();
+        data.execute_operators()
+            .map_err(|errors| errors.into_iter().map(|error| error.reason).collect::<Vec<_>>())?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_manifest() {
+        let plugin = WasmPlugin::from_bytes("echo", &module(
+            r#"[{"name": "echo", "arguments": ["string", "number", "reference"]}, {"name": "late", "priority": "last"}]"#,
+            "",
+            ECHO,
+        )).unwrap();
+
+        assert_eq!(plugin.operators(), &[
+            WasmOperatorManifest {
+                name: "echo".to_owned(),
+                arguments: vec![ArgumentKind::StringLiteral, ArgumentKind::NumberLiteral, ArgumentKind::Reference],
+                priority: OperatorPriorityRank::Middle,
+            },
+            WasmOperatorManifest { name: "late".to_owned(), arguments: vec![], priority: OperatorPriorityRank::Last },
+        ]);
+
+        assert!(matches!(
+            WasmPlugin::from_bytes("bad", &module(r#"[{"name": "bad", "arguments": ["date"]}]"#, "", ECHO)),
+            Err(WasmPluginError::InvalidManifest(_))
+        ));
+    }
+
+    #[test]
+    fn test_call() {
+        let plugin = WasmPlugin::from_bytes("echo", &module(
+            r#"[{"name": "echo", "arguments": ["string", "number", "reference"]}]"#,
+            "",
+            ECHO,
+        )).unwrap();
+
+        let data = execute(&plugin, "x:\n  y: [1, 2]\nout: (( echo \"a\" 0x10 &x.y ))").unwrap();
+
+        assert!(matches!(data.get(&path(&["out", "operator"])), Some(OperatorData::String(inner)) if inner == "echo"));
+        assert!(matches!(data.get(&path(&["out", "path"])), Some(OperatorData::String(inner)) if inner == "out"));
+        assert!(matches!(data.get(&path(&["out", "arguments", "0", "string"])), Some(OperatorData::String(inner)) if inner == "a"));
+        assert!(matches!(data.get(&path(&["out", "arguments", "1", "number"])), Some(OperatorData::Number(inner)) if *inner == 16.0));
+        assert!(matches!(data.get(&path(&["out", "arguments", "2", "reference"])), Some(OperatorData::String(inner)) if inner == "x.y"));
+        assert!(matches!(data.get(&path(&["out", "arguments", "2", "value", "1"])), Some(OperatorData::Number(inner)) if *inner == 2.0));
+    }
+
+    #[test]
+    fn test_failures() {
+        let spinning = WasmPlugin::from_bytes("spin", &module(
+            r#"[{"name": "spin"}]"#,
+            "",
+            "(loop $forever (br $forever)) (i64.const 0)",
+        )).unwrap();
+        assert!(matches!(
+            execute(&spinning, "a: (( spin ))").unwrap_err().as_slice(),
+            [OperatorExecutionErrorReason::PluginFailed(_)]
+        ));
+
+        let answer = r#"{"error": "nope"}"#;
+        let failing = WasmPlugin::from_bytes("fail", &module(
+            r#"[{"name": "fail"}]"#,
+            answer,
+            &format!("(i64.const {})", (2048 << 32 | answer.len()) as i64),
+        )).unwrap();
+        assert!(matches!(
+            execute(&failing, "a: (( fail ))").unwrap_err().as_slice(),
+            [OperatorExecutionErrorReason::PluginFailed(message)] if message.ends_with("nope")
+        ));
+
+        for (packed, expected) in [(0xffff_ffff_i64, "exceeds the limit"), (65530 << 32 | 16, "lies outside of memory")] {
+            let overflowing = WasmPlugin::from_bytes("overflow", &module(
+                r#"[{"name": "overflow"}]"#,
+                "",
+                &format!("(i64.const {})", packed),
+            )).unwrap();
+            assert!(matches!(
+                execute(&overflowing, "a: (( overflow ))").unwrap_err().as_slice(),
+                [OperatorExecutionErrorReason::PluginFailed(message)] if message.contains(expected)
+            ));
+        }
+
+        // Memory is marked before trapping, and a marked instance answers with an error.
+        let answer = r#"{"error": "dirty"}"#;
+        let trapping = WasmPlugin::from_bytes("trap", &module(
+            r#"[{"name": "trap"}]"#,
+            answer,
+            &format!(
+                "(if (i32.load8_u (i32.const 3072)) (then (return (i64.const {})))) (i32.store8 (i32.const 3072) (i32.const 1)) (unreachable)",
+                (2048 << 32 | answer.len()) as i64,
+            ),
+        )).unwrap();
+        let errors = execute(&trapping, "a: (( trap ))\nb: (( trap ))").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        for error in errors {
+            assert!(matches!(&error, OperatorExecutionErrorReason::PluginFailed(message) if !message.ends_with("dirty")), "{:?}", error);
+        }
+
+        // Arguments JSON cannot hold are not passed as null.
+        let echo = WasmPlugin::from_bytes("echo", &module(r#"[{"name": "echo", "arguments": ["reference"]}]"#, "", ECHO)).unwrap();
+        assert!(matches!(
+            execute(&echo, "x: [.nan]\nout: (( echo &x ))").unwrap_err().as_slice(),
+            [OperatorExecutionErrorReason::OtherError(message)] if message == "cannot pass NaN to a plugin, as JSON has no such number"
+        ));
+
+        assert!(matches!(
+            WasmPlugin::from_bytes("imports", &wat::parse_str(r#"(module (import "env" "f" (func)))"#).unwrap()),
+            Err(WasmPluginError::HasImports)
+        ));
+        assert!(matches!(
+            WasmPlugin::from_bytes("empty", &wat::parse_str("(module)").unwrap()),
+            Err(WasmPluginError::MissingExport("memory"))
+        ));
+    }
+}
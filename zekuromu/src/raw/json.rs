@@ -0,0 +1,31 @@
+use crate::data::{RawData, operators::as_exact_integer};
+
+/// On a single line, mapping keys being sorted.
+pub fn serialize_raw_data(data: &RawData) -> super::ResultDataSerializing {
+    serde_json::to_string(&serialize_json_value(data)?)
+        .map_err(|error| error.to_string())
+}
+
+fn serialize_json_value(data: &RawData) -> Result<serde_json::Value, String> {
+    Ok(match data {
+        RawData::Null => serde_json::Value::Null,
+        RawData::Boolean(inner) => serde_json::Value::Bool(*inner),
+        // Integers are written without a trailing `.0`.
+        RawData::Number(inner) if let Some(integer) = as_exact_integer(*inner) => serde_json::Value::Number(integer.into()),
+        RawData::Number(inner) => serde_json::Number::from_f64(*inner)
+            .map(serde_json::Value::Number)
+            .ok_or(format!("{} cannot be written as JSON", inner))?,
+        RawData::String(inner) => serde_json::Value::String(inner.clone()),
+        RawData::Sequence(inner) => serde_json::Value::Array(inner.iter().map(serialize_json_value).collect::<Result<_, _>>()?),
+        RawData::Mapping(inner) => {
+            let mut keys: Vec<_> = inner.keys().collect();
+            keys.sort();
+
+            let mut mapping = serde_json::Map::with_capacity(inner.len());
+            for key in keys {
+                mapping.insert(key.as_str().to_owned(), serialize_json_value(&inner[key])?);
+            }
+            serde_json::Value::Object(mapping)
+        },
+    })
+}
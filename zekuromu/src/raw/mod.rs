@@ -1,8 +1,10 @@
-mod yaml;
+pub(crate) mod json;
+pub(crate) mod yaml;
 
 use std::{fs::File, io::BufReader, path::Path};
 
 type ResultDataParsing = Result<crate::data::RawData, String>;
+type ResultDataSerializing = Result<String, String>;
 
 enum SupportedFiletypes {
     Yaml
@@ -16,7 +18,8 @@ pub fn parse_file_as_raw_data(path: &Path) -> ResultDataParsing {
         .ok_or("Extension is not proper UTF-8.".to_string())?;
 
     let filetype = match extension_as_utf8 {
-        "yaml" | "yml" => { SupportedFiletypes::Yaml }
+        // JSON is read as YAML, which it is a subset of.
+        "yaml" | "yml" | "json" => { SupportedFiletypes::Yaml }
         _ => { Err("File's extension does not match any supported filetype.".to_string())? }
     };
 
@@ -29,3 +32,18 @@ pub fn parse_file_as_raw_data(path: &Path) -> ResultDataParsing {
         SupportedFiletypes::Yaml => yaml::parse_as_raw_data(&mut file_buffer)
     }
 }
+
+/// Mapping keys are sorted, so that the output does not depend on hashing.
+pub fn serialize_raw_data_as_yaml(data: &crate::data::RawData) -> ResultDataSerializing {
+    yaml::serialize_raw_data(data)
+}
+
+/// On a single line, mapping keys being sorted.
+pub fn serialize_raw_data_as_json(data: &crate::data::RawData) -> ResultDataSerializing {
+    json::serialize_raw_data(data)
+}
+
+/// YAML, or JSON which is read as YAML, as files are.
+pub fn parse_str_as_raw_data(text: &str) -> ResultDataParsing {
+    yaml::parse_str_as_raw_data(text)
+}
@@ -1,12 +1,46 @@
 use std::{collections::HashMap, io::BufReader};
 
-use crate::data::RawData;
+use crate::data::{RawData, operators::as_exact_integer};
 
 pub fn parse_as_raw_data<R: std::io::Read>(buffer: &mut BufReader<R>) -> super::ResultDataParsing {
-    let raw_yaml: serde_yaml::Value = serde_yaml::from_reader(buffer).unwrap();
+    let raw_yaml: serde_yaml::Value = serde_yaml::from_reader(buffer)
+        .map_err(|error| error.to_string())?;
     parse_yaml_value(&raw_yaml)
 }
 
+pub fn parse_str_as_raw_data(text: &str) -> super::ResultDataParsing {
+    let raw_yaml: serde_yaml::Value = serde_yaml::from_str(text)
+        .map_err(|error| error.to_string())?;
+    parse_yaml_value(&raw_yaml)
+}
+
+pub fn serialize_raw_data(data: &RawData) -> super::ResultDataSerializing {
+    serde_yaml::to_string(&serialize_yaml_value(data))
+        .map_err(|error| error.to_string())
+}
+
+fn serialize_yaml_value(data: &RawData) -> serde_yaml::Value {
+    match data {
+        RawData::Null => serde_yaml::Value::Null,
+        RawData::Boolean(inner) => serde_yaml::Value::Bool(*inner),
+        // Integers are written without a trailing `.0`.
+        RawData::Number(inner) if let Some(integer) = as_exact_integer(*inner) => serde_yaml::Value::Number(integer.into()),
+        RawData::Number(inner) => serde_yaml::Value::Number((*inner).into()),
+        RawData::String(inner) => serde_yaml::Value::String(inner.clone()),
+        RawData::Sequence(inner) => serde_yaml::Value::Sequence(inner.iter().map(serialize_yaml_value).collect()),
+        RawData::Mapping(inner) => {
+            let mut keys: Vec<_> = inner.keys().collect();
+            keys.sort();
+
+            let mut mapping = serde_yaml::Mapping::with_capacity(inner.len());
+            for key in keys {
+                mapping.insert(serde_yaml::Value::String(key.as_str().to_owned()), serialize_yaml_value(&inner[key]));
+            }
+            serde_yaml::Value::Mapping(mapping)
+        },
+    }
+}
+
 fn parse_yaml_value(value: &serde_yaml::Value) -> Result<RawData, String> {
     match value {
         serde_yaml::Value::Null => Ok(RawData::Null),
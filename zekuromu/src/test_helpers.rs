@@ -0,0 +1,47 @@
+//! Shared by tests, which mostly execute operators against whole documents.
+
+use std::io::BufReader;
+
+use crate::{data::{DataKeyPath, OperatorData, OperatorDataExecutionError, RawData}, operators::OperatorExecutionErrorReason};
+
+pub(crate) fn raw_document(yaml: &str) -> RawData {
+    crate::raw::yaml::parse_as_raw_data(&mut BufReader::new(yaml.as_bytes())).unwrap()
+}
+
+pub(crate) fn document(yaml: &str) -> OperatorData {
+    raw_document(yaml).into_raw_operator_data().try_into().unwrap()
+}
+
+pub(crate) fn path(keys: &[&str]) -> DataKeyPath {
+    keys.iter().fold(DataKeyPath::empty(), |path, key| path.child(*key))
+}
+
+pub(crate) fn number_at(data: &OperatorData, keys: &[&str]) -> f64 {
+    match data.get(&path(keys)) {
+        Some(OperatorData::Number(inner)) => *inner,
+        other => panic!("expected a number at {:?}, found {:?}", keys, other),
+    }
+}
+
+pub(crate) fn string_at<'a>(data: &'a OperatorData, keys: &[&str]) -> &'a str {
+    match data.get(&path(keys)) {
+        Some(OperatorData::String(inner)) => inner,
+        other => panic!("expected a string at {:?}, found {:?}", keys, other),
+    }
+}
+
+/// The value at `keys` written as YAML, which it must hold no operator to be.
+pub(crate) fn yaml_at(data: &OperatorData, keys: &[&str]) -> String {
+    let value = data.get(&path(keys))
+        .unwrap_or_else(|| panic!("nothing found at {:?}", keys));
+    crate::raw::serialize_raw_data_as_yaml(&RawData::try_from(value.clone()).unwrap()).unwrap()
+}
+
+pub(crate) fn parsing_error(yaml: &str) -> String {
+    let result: Result<OperatorData, _> = raw_document(yaml).into_raw_operator_data().try_into();
+    result.unwrap_err().to_string()
+}
+
+pub(crate) fn reasons(errors: Vec<OperatorDataExecutionError>) -> Vec<(DataKeyPath, OperatorExecutionErrorReason)> {
+    errors.into_iter().map(|error| (error.at, error.reason)).collect()
+}